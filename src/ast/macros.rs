@@ -80,6 +80,13 @@ macro_rules! _rule_field_input_types {
         }
     };
 
+    (#[transform($($x:ty,)* try $t:ty $(, $($rest:tt)*)?)] $Field:ty $(,)?) => {
+        $crate::_rule_field_input_types! {
+            #[transform($($x,)* $crate::ast::transform::try_transform<$t> $(, $($rest)*)?)]
+            $Field
+        }
+    };
+
     (#[transform($x1:ty $(,)?)] $Field:ty $(,)?) => {
         $crate::ast::Transformed<$Field, $x1>
     };
@@ -95,7 +102,7 @@ macro_rules! _rule_field_input_types {
         ::core::compile_error!(::core::concat!("Invalid transform value ", ::core::stringify!($($x)*)))
     };
 
-    (#$attr1:tt $(#$attr:tt)? $Field:ty $(,)?) => {
+    (#$attr1:tt $(#$attr:tt)* $Field:ty $(,)?) => {
         $crate::_rule_field_input_types! {
             $(#$attr)* $Field
         }
@@ -106,12 +113,27 @@ macro_rules! _rule_field_input_types {
     };
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _rule_expected_label {
+    () => { ::core::option::Option::None };
+    (#[label($label:literal $(,)?)] $(#$attr:tt)*) => {
+        ::core::option::Option::Some($label)
+    };
+    (#$attr1:tt $(#$attr:tt)*) => {
+        $crate::_rule_expected_label! { $(#$attr)* }
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _define_rule_struct {
     (#[transform $($x:tt)*] $($rest:tt)*) => {
         $crate::_define_rule_struct! { $($rest)* }
     };
+    (#[label($label:literal $(,)?)] $($rest:tt)*) => {
+        $crate::_define_rule_struct! { $($rest)* }
+    };
     (
         $(#$attr:tt)*
         $vis:vis struct $Name:ident {$(,)?} [$($out:tt)*]
@@ -171,6 +193,9 @@ macro_rules! _define_rule_enum {
     (#[transform $($x:tt)*] $($rest:tt)*) => {
         $crate::_define_rule_enum! { $($rest)* }
     };
+    (#[label($label:literal $(,)?)] $($rest:tt)*) => {
+        $crate::_define_rule_enum! { $($rest)* }
+    };
     (
         $(#$attr:tt)*
         $vis:vis enum $Name:ident {$(,)?} [$($out:tt)*] []
@@ -285,15 +310,20 @@ macro_rules! _define_rule {
                     cx: &$crate::ast::print::PrintContext,
                     f: &mut ::core::fmt::Formatter,
                 ) -> ::core::fmt::Result {
+                    use $crate::ast::print::{FieldRef, RuleField as _, ValueField as _};
                     let Self { $($($field)?),* } = self;
                     f.write_str(::core::stringify!($Name))?;
                     f.write_str(" -> ")?;
-                    cx.debug_rule(f, [$($($field as &dyn $crate::ast::Rule,)*)*])
+                    cx.debug_fields(f, [$($((&FieldRef($field)).field(),)*)*])
                 }
 
                 fn name() -> &'static str {
                     ::core::stringify!($Name)
                 }
+
+                fn expected_label() -> ::core::option::Option<&'static str> {
+                    $crate::_rule_expected_label! { $(#$attr)* }
+                }
             }
         };
 
@@ -350,6 +380,7 @@ macro_rules! _define_rule {
                     _cx: &$crate::ast::print::PrintContext,
                     _f: &mut ::core::fmt::Formatter,
                 ) -> ::core::fmt::Result {
+                    use $crate::ast::print::{FieldRef, RuleField as _, ValueField as _};
                     match *self {$(
                         Self::$Var{ $(ref $field),* } => {
                             if _cx.is_debug() {
@@ -360,7 +391,7 @@ macro_rules! _define_rule {
                                     " -> ",
                                 ))?;
                             }
-                            _cx.debug_rule(_f, [$($field as &dyn $crate::ast::Rule),*])
+                            _cx.debug_fields(_f, [$((&FieldRef($field)).field()),*])
                         }
                     )*}
                 }
@@ -368,6 +399,10 @@ macro_rules! _define_rule {
                 fn name() -> &'static str {
                     ::core::stringify!($Name)
                 }
+
+                fn expected_label() -> ::core::option::Option<&'static str> {
+                    $crate::_rule_expected_label! { $(#$attr)* }
+                }
             }
         };
 
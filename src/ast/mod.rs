@@ -9,23 +9,26 @@ use core::{
     hash::Hash,
     marker::PhantomData,
     ops::ControlFlow::{self, Break, Continue},
+    ptr,
 };
 
 use either::{for_both, Either};
 
 use crate::{
+    cst::Cst,
     internal_prelude::*,
+    lexer::LexerMode,
     parse::{
-        CxType, Location, LocationRange, ParseContext, ParseContextParts, ParseContextUpdate,
-        ParseError, SizedParseContext,
+        CxType, Location, LocationRange, ParseContext, ParseContextUpdate, ParseError,
+        ParseErrorKind, ParseOptions, RootParseContext, TokenBufData,
     },
-    token::{AnyToken, Eof, TokenDef, TokenType},
-    utils::{default, simple_name, try_run, DebugFn, MyTry},
+    token::{AnyToken, Eof, TokenDef, TokenType, ValueTokenDef},
+    utils::{combine_id, default, simple_name, try_run, DebugFn, MyTry},
 };
 
 use self::{
     print::{PrintContext, PrintVisibility},
-    transform::{identity, TransformInto},
+    transform::{identity, TransformInto, TransformWithSource, TryTransformInto},
 };
 
 pub struct WithSource<'src, T: ?Sized> {
@@ -79,6 +82,16 @@ pub trait Rule: Any + Debug {
         f.write_str(Self::name())
     }
 
+    /// A description of this rule, like "expression", to report in errors in place of the tokens
+    /// it would expect when it fails at its start. It's set by `#[label("...")]` in
+    /// [`define_rule!`](crate::define_rule).
+    fn expected_label() -> Option<&'static str>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
     fn pre_parse<Cx: CxType>(
         cx: ParseContext<Cx>,
         state: PreParseState,
@@ -106,16 +119,23 @@ pub struct RuleType<'lt, Cx: CxType> {
     node_id: fn() -> TypeId,
     pre_parse: fn(ParseContext<Cx>, PreParseState, &RuleType<Cx>) -> RuleParseResult<()>,
     next: Option<&'lt Self>,
+    /// How many rules are in the chain starting from this one.
+    chain_len: usize,
+    /// The id of the chain after this rule, or 0 if there isn't one.
+    next_id: u64,
 }
 
 impl<'lt, Cx: CxType> RuleType<'lt, Cx> {
     pub fn new<T: Rule>(next: impl Into<Option<&'lt Self>>) -> Self {
+        let next = next.into();
         RuleType {
             name: T::name,
             print_name: T::print_name,
             node_id: TypeId::of::<T>,
             pre_parse: T::pre_parse::<Cx>,
-            next: next.into(),
+            next,
+            chain_len: next.map_or(0, |next| next.chain_len) + 1,
+            next_id: next.map_or(0, |next| next.chain_id()),
         }
     }
 
@@ -126,6 +146,8 @@ impl<'lt, Cx: CxType> RuleType<'lt, Cx> {
             node_id: TypeId::of::<T>,
             pre_parse: T::pre_parse,
             next: None,
+            chain_len: 1,
+            next_id: 0,
         }
     }
 
@@ -133,6 +155,10 @@ impl<'lt, Cx: CxType> RuleType<'lt, Cx> {
         Self::new::<ListNode<T>>(next)
     }
 
+    pub(crate) fn chain_len(&self) -> usize {
+        self.chain_len
+    }
+
     #[inline]
     pub fn name(&self) -> &str {
         (self.name)()
@@ -143,12 +169,37 @@ impl<'lt, Cx: CxType> RuleType<'lt, Cx> {
         (self.node_id)()
     }
 
+    /// Whether `rule`, which starts a chain of `chain_len` rules, is in the chain starting from
+    /// this rule.
+    ///
+    /// Chains only ever share their ends, so `rule` can only be the rule in this chain that's
+    /// followed by as many rules as it is.
+    pub(crate) fn chain_contains(&self, rule: *const (), chain_len: usize) -> bool {
+        let Some(skip) = self.chain_len.checked_sub(chain_len) else {
+            return false;
+        };
+        let mut this = self;
+        for _ in 0..skip {
+            match this.next {
+                Some(next) => this = next,
+                None => return false,
+            }
+        }
+        ptr::eq(this as *const Self as *const (), rule)
+    }
+
+    /// An id for the node ids of this rule and every rule after it, which is the same for
+    /// chains of the same rules.
+    pub(crate) fn chain_id(&self) -> u64 {
+        combine_id(self.next_id, self.node_id())
+    }
+
     #[inline]
     pub fn pre_parse(&self, cx: ParseContext<Cx>, state: PreParseState) -> RuleParseResult<()> {
         if state.dist >= cx.look_ahead().len() {
             return Ok(());
         }
-        (self.pre_parse)(cx, state, self.next.unwrap_or_default())
+        (self.pre_parse)(cx.end_modes(self), state, self.next.unwrap_or_default())
     }
 }
 
@@ -230,6 +281,10 @@ pub trait TransformRule: Any + Debug {
         simple_name::<Self>()
     }
 
+    fn expected_label() -> Option<&'static str> {
+        None
+    }
+
     fn update_context<Cx: CxType, R>(
         cx: ParseContext<Cx>,
         f: impl FnOnce(ParseContext<Cx>) -> R,
@@ -248,6 +303,9 @@ where
     fn name() -> &'static str {
         <This as TransformRule>::name()
     }
+    fn expected_label() -> Option<&'static str> {
+        <This as TransformRule>::expected_label()
+    }
     fn print_visibility(&self, cx: &PrintContext) -> PrintVisibility {
         TransformRule::print_visibility(self, cx)
     }
@@ -261,14 +319,27 @@ where
         state: PreParseState,
         next: &RuleType<Cx>,
     ) -> RuleParseResult<()> {
-        Self::update_context(cx, |cx| This::Inner::pre_parse(cx, state, next))
+        cx.enter_rule::<This, _>(state.start, true, next, |cx| match This::expected_label() {
+            Some(label) => cx.expecting(label, state.start, |cx| {
+                Self::update_context(cx, |cx| This::Inner::pre_parse(cx, state, next))
+            }),
+            None => Self::update_context(cx, |cx| This::Inner::pre_parse(cx, state, next)),
+        })
     }
 
     fn parse<Cx: CxType>(cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self>
     where
         Self: Sized,
     {
-        Self::update_context(cx, |cx| This::Inner::parse(cx, next).map(This::from_inner))
+        let start = cx.location();
+        cx.enter_rule::<This, _>(start, false, next, |cx| match This::expected_label() {
+            Some(label) => cx.expecting(label, start, |cx| {
+                Self::update_context(cx, |cx| This::Inner::parse(cx, next).map(This::from_inner))
+            }),
+            None => {
+                Self::update_context(cx, |cx| This::Inner::parse(cx, next).map(This::from_inner))
+            }
+        })
     }
 
     fn matches_empty() -> bool
@@ -279,20 +350,33 @@ where
     }
 }
 
+/// A list of `In` items transformed by `X`, separated by `Delim`, and with an optional trailing
+/// `Delim` if `TRAIL` is set.
+///
+/// There must be at least `MIN` items, and no more than `MAX` are parsed.
 pub struct TransformList<
     T,
     X: TransformInto<T>,
     Delim = Empty,
     const TRAIL: bool = false,
     const PREFER_SHORT: bool = false,
+    const MIN: usize = 0,
+    const MAX: usize = { usize::MAX },
 > {
     pub items: Vec<T>,
     _x: PhantomData<X>,
     _delim: PhantomData<Delim>,
 }
 
-impl<T, X: TransformInto<T>, Delim, const TRAIL: bool, const PREFER_SHORT: bool>
-    TransformList<T, X, Delim, TRAIL, PREFER_SHORT>
+impl<
+        T,
+        X: TransformInto<T>,
+        Delim,
+        const TRAIL: bool,
+        const PREFER_SHORT: bool,
+        const MIN: usize,
+        const MAX: usize,
+    > TransformList<T, X, Delim, TRAIL, PREFER_SHORT, MIN, MAX>
 {
     pub fn new(items: Vec<T>) -> Self {
         Self {
@@ -303,8 +387,15 @@ impl<T, X: TransformInto<T>, Delim, const TRAIL: bool, const PREFER_SHORT: bool>
     }
 }
 
-impl<T: Debug, X: TransformInto<T>, Delim, const TRAIL: bool, const PREFER_SHORT: bool> Debug
-    for TransformList<T, X, Delim, TRAIL, PREFER_SHORT>
+impl<
+        T: Debug,
+        X: TransformInto<T>,
+        Delim,
+        const TRAIL: bool,
+        const PREFER_SHORT: bool,
+        const MIN: usize,
+        const MAX: usize,
+    > Debug for TransformList<T, X, Delim, TRAIL, PREFER_SHORT, MIN, MAX>
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         Debug::fmt(&self.items, f)
@@ -441,7 +532,10 @@ impl<T: Rule, U: Rule> Rule for Either<T, U> {
         state: PreParseState,
         next: &RuleType<Cx>,
     ) -> RuleParseResult<()> {
-        T::pre_parse(cx.by_ref(), state, next).or_else(|_| U::pre_parse(cx, state, next))
+        T::pre_parse(cx.by_ref(), state, next).or_else(|err| match cx.is_aborted() {
+            true => Err(err),
+            false => U::pre_parse(cx, state, next),
+        })
     }
 
     fn parse<Cx: CxType>(mut cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self>
@@ -451,9 +545,15 @@ impl<T: Rule, U: Rule> Rule for Either<T, U> {
         let Err(err1) = cx.pre_parse::<T>(next) else {
             return T::parse(cx, next).map(Either::Left);
         };
+        if cx.is_aborted() {
+            return Err(err1);
+        }
         let Err(err2) = cx.pre_parse::<U>(next) else {
             return U::parse(cx, next).map(Either::Right);
         };
+        if cx.is_aborted() {
+            return Err(err2);
+        }
         let max_location = err1.location.max(err2.location);
 
         if err1.location == max_location {
@@ -520,9 +620,7 @@ impl TransformRule for () {
         PrintVisibility::DebugOnly
     }
 
-    fn from_inner(_: Self::Inner) -> Self {
-        ()
-    }
+    fn from_inner(_: Self::Inner) -> Self {}
 }
 
 impl<T0: Rule> TransformRule for (T0,) {
@@ -669,28 +767,30 @@ impl<T: TokenDef> Rule for Token<T> {
             return Ok(());
         }
 
-        let ParseContextParts {
-            src, look_ahead, ..
-        } = cx.as_parts();
+        if !cx.mode_allows(TokenType::of::<T>()) {
+            return Err(RuleParseFailed {
+                location: state.start,
+            });
+        }
 
-        let end = match look_ahead.get_mut(state.dist..) {
-            None | Some([]) => return Ok(()),
-            Some([Some(token), ..]) if token.token_type.token_id() == TypeId::of::<T>() => {
+        // a token looked ahead in another mode may have skipped different trivia, so it's only
+        // used if it starts in the same place
+        let start = cx.skip_trivia(state.start);
+        let end = match cx.look_ahead().get(state.dist).copied() {
+            None => return Ok(()),
+            Some(Some(token))
+                if token.token_type.token_id() == TypeId::of::<T>()
+                    && token.range.start == start =>
+            {
                 token.range.end
             }
-            Some([token, ..]) => {
-                let Some(range) = T::try_lex(src, state.start) else {
-                    cx.error_mut()
-                        .add_expected(state.start, TokenType::of::<T>());
-                    return Err(RuleParseFailed {
-                        location: state.start,
-                    });
+            Some(_) => {
+                let Some(token) = cx.lex(TokenType::of::<T>(), start)? else {
+                    cx.error_mut().add_expected(start, TokenType::of::<T>());
+                    return Err(RuleParseFailed { location: start });
                 };
-                *token = Some(AnyToken {
-                    token_type: TokenType::of::<T>(),
-                    range,
-                });
-                range.end
+                cx.fill_look_ahead(state.dist, token);
+                token.range.end
             }
         };
 
@@ -708,21 +808,37 @@ impl<T: TokenDef> Rule for Token<T> {
     where
         Self: Sized,
     {
-        let location = cx.location();
+        let location = cx.skip_trivia(cx.location());
+        if !cx.mode_allows(TokenType::of::<T>()) {
+            return Err(RuleParseFailed { location });
+        }
+        cx.reach(location)?;
 
         try_run(|| {
-            if let [Some(token), ..] = **cx.look_ahead() {
-                if token.token_type.token_id() != TypeId::of::<T>() {
-                    return Err(RuleParseFailed { location });
+            match **cx.look_ahead() {
+                // looked ahead in a mode that skipped different trivia, so none of the tokens
+                // can be relied on
+                [Some(token), ..] if token.range.start != location => {
+                    *cx.look_ahead_mut() = cx.look_ahead().cleared();
                 }
-                cx.advance();
-                return Ok(token.range.into());
+                [Some(token), ..] => {
+                    if token.token_type.token_id() != TypeId::of::<T>() {
+                        return Err(RuleParseFailed { location });
+                    }
+                    cx.advance();
+                    cx.record_token(token);
+                    return Ok(token.range.into());
+                }
+                _ => {}
             }
 
-            let range = T::try_lex(cx.src(), location).ok_or(RuleParseFailed { location })?;
-            cx.set_location(range.end);
+            let token = cx
+                .lex(TokenType::of::<T>(), location)?
+                .ok_or(RuleParseFailed { location })?;
+            cx.set_location(token.range.end);
+            cx.record_token(token);
 
-            Ok(range.into())
+            Ok(token.range.into())
         })
         .break_also(|err| {
             cx.error_mut()
@@ -731,6 +847,57 @@ impl<T: TokenDef> Rule for Token<T> {
     }
 }
 
+/// The value of a [`ValueTokenDef`], converted from the text of the token.
+///
+/// If the conversion fails, the parse fails with [`ParseErrorKind::Invalid`] at the token, as by
+/// [`ParseContext::reject`], unless it's only being parsed speculatively, in which case it fails
+/// like any other mismatch.
+pub struct TokenValue<T: ValueTokenDef> {
+    pub value: T::Value,
+}
+
+impl<T: ValueTokenDef> Debug for TokenValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T: ValueTokenDef> Rule for TokenValue<T> {
+    fn print_name(f: &mut Formatter) -> fmt::Result
+    where
+        Self: Sized,
+    {
+        f.write_str(T::display_name())
+    }
+
+    fn matches_empty() -> bool {
+        false
+    }
+
+    fn pre_parse<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        Token::<T>::pre_parse(cx, state, next)
+    }
+
+    fn parse<Cx: CxType>(mut cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self>
+    where
+        Self: Sized,
+    {
+        let Token { range, .. } = Token::<T>::parse(cx.by_ref(), next)?;
+
+        match T::convert(range.slice(cx.src())) {
+            Ok(value) => Ok(Self { value }),
+            Err(_) if cx.is_speculative() => Err(RuleParseFailed {
+                location: range.start,
+            }),
+            Err(message) => Err(cx.reject(range, message)),
+        }
+    }
+}
+
 impl<T: Rule> TransformRule for PhantomData<T> {
     type Inner = T;
 
@@ -775,8 +942,8 @@ macro_rules! generic_unit {
         }
         impl<$($T: ?Sized),*> Eq for $Name<$($T),*> {}
         impl<$($T: ?Sized),*> PartialOrd for $Name<$($T),*> {
-            fn partial_cmp(&self, _: &Self) -> Option<Ordering> {
-                Some(Ordering::Equal)
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
             }
         }
         impl<$($T: ?Sized),*> Ord for $Name<$($T),*> {
@@ -822,6 +989,38 @@ impl Rule for Accept {
     }
 }
 
+/// Commits to the rule it's in once everything before it has matched, e.g. after the `fn` of a
+/// function definition.
+///
+/// Lookahead stops at a cut, so an [`Either`] picks the alternative with the cut, and an
+/// [`Option`] or list its item, without checking what follows. If that then fails to parse, the
+/// error is reported where it failed rather than other alternatives being tried.
+///
+/// [`Backtrack`], which parses its rule in full to check it, also commits to it once it passes a
+/// cut: if the rule fails after the cut, the error is reported there rather than being
+/// backtracked past.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cut;
+
+impl Rule for Cut {
+    fn print_visibility(&self, _: &PrintContext) -> PrintVisibility {
+        PrintVisibility::Never
+    }
+
+    fn pre_parse<Cx: CxType>(
+        _: ParseContext<Cx>,
+        _: PreParseState,
+        _: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        Ok(())
+    }
+
+    fn parse<Cx: CxType>(mut cx: ParseContext<Cx>, _: &RuleType<Cx>) -> RuleParseResult<Self> {
+        cx.pass_cut();
+        Ok(Self)
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DualParse<Outer, Inner> {
     pub outer: Outer,
@@ -867,7 +1066,7 @@ impl<Outer: Rule, Inner: Rule> Rule for DualParse<Outer, Inner> {
     where
         Self: Sized,
     {
-        let mut look_ahead = *cx.look_ahead();
+        let mut look_ahead = cx.look_ahead().clone();
         Outer::pre_parse(cx.by_ref(), state, next)?;
         Inner::pre_parse(
             cx.by_ref().update(ParseContextUpdate {
@@ -888,14 +1087,19 @@ impl<Outer: Rule, Inner: Rule> Rule for DualParse<Outer, Inner> {
 
         let (outer, end) = <(Outer, Location)>::parse(cx.by_ref(), next)?;
 
-        let (inner, _) = match cx.by_ref().update(ParseContextUpdate {
-            src: Some(&src[..end.position]),
-            location: Some(&mut start.clone()),
-            look_ahead: Some(&mut default()),
-            ..default()
-        }) {
-            cx => <(Inner, Silent<Token<Eof>>)>::parse(cx, default())?,
-        };
+        // the inner parse covers the same tokens again, so only those of the outer one are kept
+        let mark = cx.token_mark();
+        let mut look_ahead = cx.look_ahead().cleared();
+        let (inner, _) = <(Inner, Silent<Token<Eof>>)>::parse(
+            cx.by_ref().update(ParseContextUpdate {
+                src: Some(&src[..end.position]),
+                location: Some(&mut start.clone()),
+                look_ahead: Some(&mut look_ahead),
+                ..default()
+            }),
+            default(),
+        )?;
+        cx.rollback_tokens(mark);
 
         if end > start {
             cx.set_location(end);
@@ -946,14 +1150,15 @@ impl<T: Rule> Rule for CompoundToken<T> {
                 token.range.end
             }
             Some(_) => {
-                let (_, end) = cx.isolated_parse::<Discard<T>>(state.start, next)?;
-                cx.look_ahead_mut()[state.dist] = Some(AnyToken {
+                let end = cx.isolated_parse::<Discard<T>>(state.start, next)?;
+                let token = AnyToken {
                     token_type: TokenType::of::<CompoundTokenDef<T>>(),
                     range: LocationRange {
                         start: state.start,
                         end,
                     },
-                });
+                };
+                cx.fill_look_ahead(state.dist, token);
                 end
             }
             None => return Ok(()),
@@ -979,9 +1184,10 @@ impl<T: Rule> Rule for CompoundToken<T> {
                 return Err(RuleParseFailed { location });
             }
             Some(_) => {
+                let mut look_ahead = cx.look_ahead().cleared();
                 let value = T::parse(
                     cx.by_ref().update(ParseContextUpdate {
-                        look_ahead: Some(&mut default()),
+                        look_ahead: Some(&mut look_ahead),
                         ..default()
                     }),
                     next,
@@ -996,7 +1202,160 @@ impl<T: Rule> Rule for CompoundToken<T> {
     }
 }
 
+/// Parses `T` with only the tokens allowed by the mode `M` being lexed.
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PushMode<M, T> {
+    pub value: T,
+    _m: PhantomData<M>,
+}
+
+impl<M, T: Debug> Debug for PushMode<M, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<M: LexerMode, T: Rule> TransformRule for PushMode<M, T> {
+    type Inner = T;
+
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        f.write_str("PushMode(")?;
+        T::print_name(f)?;
+        f.write_str(")")
+    }
+
+    fn print_visibility(&self, cx: &PrintContext) -> PrintVisibility {
+        self.value.print_visibility(cx)
+    }
+
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        self.value.print_tree(cx, f)
+    }
+
+    fn from_inner(value: Self::Inner) -> Self {
+        Self {
+            value,
+            _m: PhantomData,
+        }
+    }
+
+    fn update_context<Cx: CxType, R>(
+        cx: ParseContext<Cx>,
+        f: impl FnOnce(ParseContext<Cx>) -> R,
+    ) -> R {
+        cx.push_mode::<M, _>(f)
+    }
+}
+
+/// Parses `T` in the mode that was active before the current one, e.g. for an expression
+/// interpolated into a string.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PopMode<T> {
+    pub value: T,
+}
+
+impl<T: Rule> TransformRule for PopMode<T> {
+    type Inner = T;
+
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        f.write_str("PopMode(")?;
+        T::print_name(f)?;
+        f.write_str(")")
+    }
+
+    fn print_visibility(&self, cx: &PrintContext) -> PrintVisibility {
+        self.value.print_visibility(cx)
+    }
+
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        self.value.print_tree(cx, f)
+    }
+
+    fn from_inner(value: Self::Inner) -> Self {
+        Self { value }
+    }
+
+    fn update_context<Cx: CxType, R>(
+        cx: ParseContext<Cx>,
+        f: impl FnOnce(ParseContext<Cx>) -> R,
+    ) -> R {
+        cx.pop_mode(f)
+    }
+}
+
+/// Parses `T` without skipping the trivia set by [`ParseOptions::trivia`] before its tokens, e.g.
+/// for the contents of a string literal.
+///
+/// Trivia is still skipped before `T` itself, and after it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NoTrivia<T> {
+    pub value: T,
+}
+
+/// Marks where a [`NoTrivia`] ends in the rules that follow it, so they skip trivia again.
+#[derive(Debug)]
+struct ResumeTrivia;
+
+impl Rule for ResumeTrivia {
+    fn pre_parse<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        next.pre_parse(cx.resume_trivia(), state)
+    }
+
+    fn parse<Cx: CxType>(cx: ParseContext<Cx>, _: &RuleType<Cx>) -> RuleParseResult<Self> {
+        Err(RuleParseFailed {
+            location: cx.location(),
+        })
+    }
+}
+
+impl<T: Rule> Rule for NoTrivia<T> {
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        f.write_str("NoTrivia(")?;
+        T::print_name(f)?;
+        f.write_str(")")
+    }
+
+    fn print_visibility(&self, cx: &PrintContext) -> PrintVisibility {
+        self.value.print_visibility(cx)
+    }
+
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        self.value.print_tree(cx, f)
+    }
+
+    fn pre_parse<Cx: CxType>(
+        mut cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        let start = cx.skip_trivia(state.start);
+        T::pre_parse(
+            cx.suspend_trivia(),
+            PreParseState { start, ..state },
+            &RuleType::new::<ResumeTrivia>(next),
+        )
+    }
+
+    fn parse<Cx: CxType>(mut cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        let start = cx.skip_trivia(cx.location());
+        cx.set_location(start);
+        let value = T::parse(cx.suspend_trivia(), &RuleType::new::<ResumeTrivia>(next))?;
+        Ok(Self { value })
+    }
+
+    fn matches_empty() -> bool {
+        T::matches_empty()
+    }
+}
+
 /// Ignore the lookahead buffer altogether and just try parsing it to see if it matches.
+///
+/// If it fails after passing a [`Cut`], it's committed to anyway, so the error is reported where
+/// it failed.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Backtrack<T> {
     pub value: T,
@@ -1011,7 +1370,10 @@ impl<T: Rule> Rule for Backtrack<T> {
     where
         Self: Sized,
     {
-        let (_, end) = cx.isolated_parse::<(Discard<T>,)>(state.start, next)?;
+        // it failed after a cut, which commits to it just as lookahead would
+        let Some(end) = cx.isolated_parse_until_cut::<(Discard<T>,)>(state.start, next)? else {
+            return Ok(());
+        };
         next.pre_parse(
             cx,
             PreParseState {
@@ -1025,9 +1387,10 @@ impl<T: Rule> Rule for Backtrack<T> {
     where
         Self: Sized,
     {
+        let mut look_ahead = cx.look_ahead().cleared();
         let value = T::parse(
             cx.update(ParseContextUpdate {
-                look_ahead: Some(&mut default()),
+                look_ahead: Some(&mut look_ahead),
                 ..default()
             }),
             next,
@@ -1096,6 +1459,8 @@ impl Rule for Location {
     }
 }
 
+/// Consumes the rest of the input, returning its range. To get the range of a rule, use
+/// [`Spanned`] instead.
 impl Rule for LocationRange {
     fn pre_parse<Cx: CxType>(
         cx: ParseContext<Cx>,
@@ -1135,44 +1500,153 @@ impl Rule for LocationRange {
             "{}..{} => {:?}",
             self.start.position,
             self.end.position,
-            cx.src()
-                .get(self.start.position..self.end.position)
-                .unwrap_or_default()
+            self.slice(cx.src())
         )
     }
 }
 
-generic_unit!(
-    pub struct Discard<T>;
-    pub struct Ignore<T>;
-);
-
-impl<T: Rule> TransformRule for Discard<T> {
-    type Inner = T;
+/// A rule along with the range of the source it was parsed from.
+///
+/// The range covers the tokens of `T`, so any trivia before or after it is left out, including
+/// what's skipped by [`Ignore`]. If `T` is empty, so is the range, at where `T` would start.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: LocationRange,
+}
 
+impl<T: Rule> Rule for Spanned<T> {
     fn print_name(f: &mut Formatter) -> fmt::Result {
-        f.write_str("Discard(")?;
+        f.write_str("Spanned(")?;
         T::print_name(f)?;
         f.write_str(")")
     }
 
-    fn from_inner(_: Self::Inner) -> Self {
-        default()
+    fn print_visibility(&self, cx: &PrintContext) -> PrintVisibility {
+        self.value.print_visibility(cx)
     }
 
-    fn print_tree(&self, _: &PrintContext, f: &mut Formatter) -> fmt::Result {
-        write!(f, "Discard<{}>", T::name())
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        self.value.print_tree(cx, f)
     }
 
-    fn print_visibility(&self, _: &PrintContext) -> PrintVisibility {
-        PrintVisibility::DebugOnly
+    fn pre_parse<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        T::pre_parse(cx, state, next)
     }
 
-    fn update_context<Cx: CxType, R>(
-        cx: ParseContext<Cx>,
-        f: impl FnOnce(ParseContext<Cx>) -> R,
-    ) -> R {
-        f(cx.discarding())
+    fn parse<Cx: CxType>(mut cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        let (value, span) = cx.spanning(true, |cx| T::parse(cx, next));
+        let value = value?;
+        let span = span.unwrap_or_else(|| {
+            let start = cx.skip_trivia(cx.location());
+            LocationRange { start, end: start }
+        });
+        Ok(Self { value, span })
+    }
+
+    fn matches_empty() -> bool {
+        T::matches_empty()
+    }
+}
+
+/// Parses `T`, keeping only the range of the source it covers rather than its structure, e.g. to
+/// take a dotted path as a whole.
+///
+/// Like [`Spanned`], the range leaves out trivia before and after `T`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Recognize<T> {
+    pub range: LocationRange,
+    _t: PhantomData<T>,
+}
+
+impl<T> Recognize<T> {
+    /// Returns the text that was parsed from `src`.
+    pub fn text<'src>(&self, src: &'src str) -> &'src str {
+        self.range.slice(src)
+    }
+}
+
+impl<T> Copy for Recognize<T> {}
+
+impl<T> Clone for Recognize<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Debug for Recognize<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Recognize").field(&self.range).finish()
+    }
+}
+
+impl<T: Rule> Rule for Recognize<T> {
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        f.write_str("Recognize(")?;
+        T::print_name(f)?;
+        f.write_str(")")
+    }
+
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.text(cx.src()))
+    }
+
+    fn pre_parse<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        T::pre_parse(cx, state, next)
+    }
+
+    fn parse<Cx: CxType>(cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        let Spanned { span, .. } = Spanned::<T>::parse(cx, next)?;
+        Ok(Self {
+            range: span,
+            _t: PhantomData,
+        })
+    }
+
+    fn matches_empty() -> bool {
+        T::matches_empty()
+    }
+}
+
+generic_unit!(
+    pub struct Discard<T>;
+    pub struct Ignore<T>;
+);
+
+impl<T: Rule> TransformRule for Discard<T> {
+    type Inner = T;
+
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        f.write_str("Discard(")?;
+        T::print_name(f)?;
+        f.write_str(")")
+    }
+
+    fn from_inner(_: Self::Inner) -> Self {
+        default()
+    }
+
+    fn print_tree(&self, _: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Discard<{}>", T::name())
+    }
+
+    fn print_visibility(&self, _: &PrintContext) -> PrintVisibility {
+        PrintVisibility::DebugOnly
+    }
+
+    fn update_context<Cx: CxType, R>(
+        cx: ParseContext<Cx>,
+        f: impl FnOnce(ParseContext<Cx>) -> R,
+    ) -> R {
+        f(cx.discarding())
     }
 }
 
@@ -1196,6 +1670,14 @@ impl<T: Rule> TransformRule for Ignore<T> {
     fn print_visibility(&self, _: &PrintContext) -> PrintVisibility {
         PrintVisibility::Never
     }
+
+    fn update_context<Cx: CxType, R>(
+        mut cx: ParseContext<Cx>,
+        f: impl FnOnce(ParseContext<Cx>) -> R,
+    ) -> R {
+        // what's ignored is trivia, so it isn't part of the span of a `Spanned`
+        cx.spanning(false, f).0
+    }
 }
 
 impl<B: Rule, C: Rule> Rule for ControlFlow<B, C> {
@@ -1279,6 +1761,12 @@ impl<T: Rule> TransformRule for ListNode<T> {
             },
         }
     }
+    fn update_context<Cx: CxType, R>(
+        cx: ParseContext<Cx>,
+        f: impl FnOnce(ParseContext<Cx>) -> R,
+    ) -> R {
+        f(cx.repeating())
+    }
 }
 
 #[derive(Debug)]
@@ -1338,23 +1826,254 @@ impl<T, X> Debug for Transformed<T, X> {
     }
 }
 
-impl<In: Rule, Out: 'static, X: TransformInto<Out, Input = In> + 'static> TransformRule
+impl<In: Rule, Out: 'static, X: TransformInto<Out, Input = In> + 'static> Rule
     for Transformed<Out, X>
 {
-    type Inner = In;
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        In::print_name(f)
+    }
 
-    fn from_inner(input: Self::Inner) -> Self {
-        Self {
-            value: X::transform(input),
+    fn pre_parse<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        In::pre_parse(cx, state, next)
+    }
+
+    fn parse<Cx: CxType>(cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        Ok(Self {
+            value: X::transform(In::parse(cx, next)?),
+            _x: PhantomData,
+        })
+    }
+
+    fn matches_empty() -> bool {
+        In::matches_empty()
+    }
+}
+
+/// The result of the [`TransformWithSource`] `X`, which is given the source as it's parsed.
+pub struct SourceTransformed<T, X> {
+    pub value: T,
+    _x: PhantomData<X>,
+}
+
+impl<T, X> Debug for SourceTransformed<T, X> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourceTransformed").finish_non_exhaustive()
+    }
+}
+
+impl<In: Rule, Out: 'static, X: TransformWithSource<Out, Input = In> + 'static> Rule
+    for SourceTransformed<Out, X>
+{
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        In::print_name(f)
+    }
+
+    fn pre_parse<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        In::pre_parse(cx, state, next)
+    }
+
+    fn parse<Cx: CxType>(cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        let src = cx.src();
+        Ok(Self {
+            value: X::transform(In::parse(cx, next)?, src),
             _x: PhantomData,
+        })
+    }
+
+    fn matches_empty() -> bool {
+        In::matches_empty()
+    }
+}
+
+/// The result of the [`TryTransformInto`] `X`, which is checked as it's parsed.
+///
+/// If `X` rejects its input, the parse fails with [`ParseErrorKind::Invalid`] over the range of
+/// the input, as by [`ParseContext::reject`], even if it was only parsed speculatively. With
+/// `BACKTRACK`, the input is instead parsed and checked ahead of time, like [`Backtrack`], so that
+/// a rejected input fails like any other mismatch, e.g. letting an [`Either`] try its other
+/// alternative. The value from that check is kept, so `X` is only applied once.
+pub struct TryTransformed<T, X, const BACKTRACK: bool = false> {
+    pub value: T,
+    _x: PhantomData<X>,
+}
+
+impl<T, X, const BACKTRACK: bool> Debug for TryTransformed<T, X, BACKTRACK> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TryTransformed").finish_non_exhaustive()
+    }
+}
+
+impl<
+        In: Rule,
+        Out: 'static,
+        X: TryTransformInto<Out, Input = In> + 'static,
+        const BACKTRACK: bool,
+    > Rule for TryTransformed<Out, X, BACKTRACK>
+{
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        In::print_name(f)
+    }
+
+    fn pre_parse<Cx: CxType>(
+        mut cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        if !BACKTRACK {
+            return In::pre_parse(cx, state, next);
+        }
+
+        let end = cx.isolated_parse::<(TryTransformCheck<Out, X>,)>(state.start, next)?;
+        next.pre_parse(
+            cx,
+            PreParseState {
+                start: end,
+                ..state
+            },
+        )
+    }
+
+    fn parse<Cx: CxType>(mut cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        let Spanned { value, span } = match BACKTRACK {
+            true => Backtrack::<Spanned<In>>::parse(cx.by_ref(), next)?.value,
+            false => Spanned::<In>::parse(cx.by_ref(), next)?,
+        };
+
+        // the value was already checked by `pre_parse`, unless it wasn't needed to decide
+        // between alternatives
+        let checked = match BACKTRACK {
+            true => cx.take_checked::<TryTransformCheck<Out, X>, Out>(span),
+            false => None,
+        };
+        match checked.map_or_else(|| X::try_transform(value, cx.src()), Ok) {
+            Ok(value) => Ok(Self {
+                value,
+                _x: PhantomData,
+            }),
+            Err(_) if BACKTRACK && cx.is_speculative() => Err(RuleParseFailed {
+                location: span.start,
+            }),
+            Err(message) => Err(cx.reject(span, message)),
+        }
+    }
+
+    fn matches_empty() -> bool {
+        In::matches_empty()
+    }
+}
+
+/// Checks the input of a backtracking [`TryTransformed`] ahead of time, keeping its value for
+/// when it's parsed.
+struct TryTransformCheck<T, X> {
+    _x: PhantomData<(T, X)>,
+}
+
+impl<T, X> Debug for TryTransformCheck<T, X> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TryTransformCheck").finish_non_exhaustive()
+    }
+}
+
+impl<In: Rule, Out: 'static, X: TryTransformInto<Out, Input = In> + 'static> Rule
+    for TryTransformCheck<Out, X>
+{
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        In::print_name(f)
+    }
+
+    fn pre_parse<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        In::pre_parse(cx, state, next)
+    }
+
+    fn parse<Cx: CxType>(mut cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        let Spanned { value, span } = Backtrack::<Spanned<In>>::parse(cx.by_ref(), next)?.value;
+        match X::try_transform(value, cx.src()) {
+            Ok(value) => {
+                cx.keep_checked::<Self, _>(span, value);
+                Ok(Self { _x: PhantomData })
+            }
+            Err(_) => Err(RuleParseFailed {
+                location: span.start,
+            }),
         }
     }
+
+    fn matches_empty() -> bool {
+        In::matches_empty()
+    }
+}
+
+/// One of the items at the end of a list with a maximum length, which can each end the list.
+///
+/// It's only used for lookahead, in a chain of them for the items that are left before the
+/// maximum.
+struct BoundedListNode<T> {
+    _t: PhantomData<T>,
+}
+
+impl<T> Debug for BoundedListNode<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoundedListNode").finish_non_exhaustive()
+    }
 }
 
-type DelimitedListPrototypeTail<T, Delim, Trailing> = (ListNode<(Delim, T)>, Trailing);
+impl<T: Rule> Rule for BoundedListNode<T> {
+    fn pre_parse<Cx: CxType>(
+        mut cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        // ending the list skips the rest of the chain
+        let mut end = next;
+        while let (true, Some(after)) = (end.node_id() == TypeId::of::<Self>(), end.next) {
+            end = after;
+        }
+
+        let item = |cx: ParseContext<Cx>| T::pre_parse(cx, state, next);
+        let end = |cx: ParseContext<Cx>| end.pre_parse(cx, state);
+        if cx.prefer_continue() {
+            item(cx.by_ref()).or_else(|err| match cx.is_aborted() {
+                true => Err(err),
+                false => end(cx),
+            })
+        } else {
+            end(cx.by_ref()).or_else(|err| match cx.is_aborted() {
+                true => Err(err),
+                false => item(cx),
+            })
+        }
+    }
+
+    fn parse<Cx: CxType>(cx: ParseContext<Cx>, _: &RuleType<Cx>) -> RuleParseResult<Self> {
+        Err(RuleParseFailed {
+            location: cx.location(),
+        })
+    }
+}
 
-type DelimitedListPrototype<T, Delim, Trailing> =
-    Option<(T, DelimitedListPrototypeTail<T, Delim, Trailing>)>;
+/// Calls `f` with a chain of `count` of `T` followed by `next`.
+fn chain<Cx: CxType, T: Rule, R>(
+    count: usize,
+    next: &RuleType<Cx>,
+    f: impl FnOnce(&RuleType<Cx>) -> R,
+) -> R {
+    match count {
+        0 => f(next),
+        _ => chain::<Cx, T, R>(count - 1, &RuleType::new::<T>(next), f),
+    }
+}
 
 #[derive(Debug)]
 struct DelimitedListTailTrailing<T, Delim> {
@@ -1383,8 +2102,130 @@ type DelimitedListTail<T, Delim> = ListNode<(Discard<Delim>, T)>;
 pub type DelimitedList<T, Delim, const TRAIL: bool = true> =
     TransformList<T, identity, Delim, TRAIL>;
 
-impl<Out, In, X, Delim, const TRAIL: bool, const PREFER_SHORT: bool> Rule
-    for TransformList<Out, X, Delim, TRAIL, PREFER_SHORT>
+/// `T` repeated at least `MIN` and at most `MAX` times, separated by `Delim` as in a
+/// [`DelimitedList`].
+///
+/// If there are too few items, the error is reported where the next one was expected.
+pub type Repeat<
+    T,
+    const MIN: usize,
+    const MAX: usize = { usize::MAX },
+    Delim = Empty,
+    const TRAIL: bool = false,
+> = TransformList<T, identity, Delim, TRAIL, false, MIN, MAX>;
+
+/// One or more `T`, separated by `Delim` as in a [`DelimitedList`].
+pub type NonEmpty<T, Delim = Empty, const TRAIL: bool = false> =
+    Repeat<T, 1, { usize::MAX }, Delim, TRAIL>;
+
+impl<
+        Out,
+        In,
+        X,
+        Delim,
+        const TRAIL: bool,
+        const PREFER_SHORT: bool,
+        const MIN: usize,
+        const MAX: usize,
+    > TransformList<Out, X, Delim, TRAIL, PREFER_SHORT, MIN, MAX>
+where
+    Out: Rule,
+    In: Rule,
+    X: TransformInto<Out, Input = In> + 'static,
+    Delim: Rule,
+{
+    /// Looks ahead over the items, followed by `Trailing` if there are any.
+    fn pre_parse_items<Cx: CxType, Trailing: Rule>(
+        mut cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        let trailing = RuleType::new::<Trailing>(next);
+        const {
+            assert!(
+                MIN <= MAX,
+                "a list can't require more items than its maximum"
+            )
+        };
+        let unbounded = RuleType::new::<ListNode<(Delim, In)>>(&trailing);
+        let (tail, optional) = match MAX {
+            usize::MAX => (&unbounded, 0),
+            _ => (&trailing, MAX - MIN.max(1)),
+        };
+
+        let items = |cx: ParseContext<Cx>| {
+            chain::<Cx, BoundedListNode<(Delim, In)>, _>(optional, tail, |tail| {
+                chain::<Cx, (Delim, In), _>(MIN.saturating_sub(1), tail, |tail| {
+                    In::pre_parse(cx, state, tail)
+                })
+            })
+        };
+
+        if MIN > 0 {
+            return items(cx);
+        }
+        items(cx.by_ref()).or_else(|err| match cx.is_aborted() {
+            true => Err(err),
+            false => next.pre_parse(cx, state),
+        })
+    }
+
+    /// Parses the first item, which is only optional if `MIN` is 0.
+    fn parse_first<Cx: CxType, Tail: Rule>(
+        cx: ParseContext<Cx>,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<Option<In>> {
+        if MIN > 0 {
+            return Partial::<In, Tail>::parse(cx, next).map(|item| Some(item.value));
+        }
+
+        Ok(
+            match ControlFlow::<(), Partial<In, Tail>>::parse(cx, next)? {
+                Continue(Partial { value, .. }) => Some(value),
+                Break(()) => None,
+            },
+        )
+    }
+
+    /// Parses the item after the `count` items so far, which is only optional once there are
+    /// `MIN` of them.
+    fn parse_next<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        next: &RuleType<Cx>,
+        count: usize,
+    ) -> RuleParseResult<Option<In>> {
+        Ok(match (count < MIN, TRAIL) {
+            (true, true) => Some(
+                <(
+                    Discard<Delim>,
+                    Partial<In, DelimitedListTailTrailing<In, Delim>>,
+                )>::parse(cx, next)?
+                .1
+                .value,
+            ),
+            (true, false) => Some(
+                <(Discard<Delim>, Partial<In, DelimitedListTail<In, Delim>>)>::parse(cx, next)?
+                    .1
+                    .value,
+            ),
+            (false, true) => DelimitedListTailTrailing::<In, Delim>::parse(cx, next)?.value,
+            (false, false) => DelimitedListTail::<In, Delim>::parse(cx, next)?
+                .value
+                .map(|(_, item)| item),
+        })
+    }
+}
+
+impl<
+        Out,
+        In,
+        X,
+        Delim,
+        const TRAIL: bool,
+        const PREFER_SHORT: bool,
+        const MIN: usize,
+        const MAX: usize,
+    > Rule for TransformList<Out, X, Delim, TRAIL, PREFER_SHORT, MIN, MAX>
 where
     Out: Rule,
     In: Rule,
@@ -1411,10 +2252,14 @@ where
     where
         Self: Sized,
     {
+        if MAX == 0 {
+            return Empty::pre_parse(cx, state, next);
+        }
+
         if TRAIL {
-            DelimitedListPrototype::<Out, Delim, Option<Delim>>::pre_parse(cx, state, next)
+            Self::pre_parse_items::<Cx, Option<Delim>>(cx, state, next)
         } else {
-            DelimitedListPrototype::<Out, Delim, Empty>::pre_parse(cx, state, next)
+            Self::pre_parse_items::<Cx, Empty>(cx, state, next)
         }
     }
 
@@ -1422,6 +2267,12 @@ where
     where
         Self: Sized,
     {
+        const {
+            assert!(
+                MIN <= MAX,
+                "a list can't require more items than its maximum"
+            )
+        };
         cx = cx.update(ParseContextUpdate {
             prefer_continue: Some(!PREFER_SHORT),
             ..default()
@@ -1429,50 +2280,85 @@ where
         let mut out = Vec::new();
         let discard = cx.should_discard();
 
-        if TRAIL {
-            let Continue(Partial { value: first, .. }) = ControlFlow::<
-                (),
-                Partial<In, DelimitedListTailTrailing<In, Delim>>,
-            >::parse(cx.by_ref(), next)?
-            else {
-                return Ok(Self::new(out));
-            };
-
-            if !discard {
-                out.push(X::transform(first));
-            }
+        if MAX == 0 {
+            return Ok(Self::new(out));
+        }
 
-            while let Some(item) =
-                DelimitedListTailTrailing::<In, Delim>::parse(cx.by_ref(), next)?.value
-            {
-                if !discard {
-                    out.push(X::transform(item));
-                }
-            }
+        let first = if TRAIL {
+            Self::parse_first::<Cx, DelimitedListTailTrailing<In, Delim>>(cx.by_ref(), next)?
         } else {
-            let Continue(Partial { value: first, .. }) = ControlFlow::<
-                (),
-                Partial<In, DelimitedListTail<In, Delim>>,
-            >::parse(cx.by_ref(), next)?
-            else {
+            Self::parse_first::<Cx, DelimitedListTail<In, Delim>>(cx.by_ref(), next)?
+        };
+        let Some(first) = first else {
+            return Ok(Self::new(out));
+        };
+
+        if !discard {
+            out.push(X::transform(first));
+        }
+
+        let mut count = 1;
+        while count < MAX {
+            let Some(item) = Self::parse_next(cx.by_ref(), next, count)? else {
                 return Ok(Self::new(out));
             };
 
             if !discard {
-                out.push(X::transform(first));
+                out.push(X::transform(item));
             }
+            count += 1;
+        }
 
-            while let Some((_, item)) =
-                DelimitedListTail::<In, Delim>::parse(cx.by_ref(), next)?.value
-            {
-                if !discard {
-                    out.push(X::transform(item));
-                }
-            }
+        if TRAIL {
+            Option::<Discard<Delim>>::parse(cx, next)?;
         }
 
         Ok(Self::new(out))
     }
+
+    fn matches_empty() -> bool {
+        MIN == 0 || MAX == 0 || In::matches_empty()
+    }
+}
+
+/// Exactly `N` of `T`, parsed as a [`Repeat`].
+impl<T: Rule, const N: usize> Rule for [T; N] {
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        f.write_str("[")?;
+        T::print_name(f)?;
+        write!(f, "; {N}]")
+    }
+
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        cx.debug_list(f, self.iter().map(|item| item as _))
+    }
+
+    fn pre_parse<Cx: CxType>(
+        cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        Repeat::<T, N, N>::pre_parse(cx, state, next)
+    }
+
+    fn parse<Cx: CxType>(cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        let location = cx.location();
+        // the items are kept even when discarding, since there's no array without them
+        let items = Repeat::<T, N, N>::parse(
+            cx.update(ParseContextUpdate {
+                discard: Some(false),
+                ..default()
+            }),
+            next,
+        )?
+        .items;
+
+        items.try_into().map_err(|_| RuleParseFailed { location })
+    }
+
+    fn matches_empty() -> bool {
+        N == 0 || T::matches_empty()
+    }
 }
 
 #[derive(Debug)]
@@ -1521,6 +2407,239 @@ impl<T: Rule, Op: Rule> TransformRule for InfixChain<T, Op> {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Associativity {
+    /// `a + b + c` parses as `(a + b) + c`
+    #[default]
+    Left,
+    /// `a ^ b ^ c` parses as `a ^ (b ^ c)`
+    Right,
+}
+
+/// A binary operator used by [`Precedence`]. Operators with a higher precedence bind tighter.
+pub trait InfixOperator: Rule {
+    fn precedence(&self) -> u32;
+
+    fn associativity(&self) -> Associativity {
+        Associativity::Left
+    }
+}
+
+/// An operator that comes before the operand in a [`Precedence`] expression, like `-x`.
+pub trait PrefixOperator: Rule {
+    fn precedence(&self) -> u32;
+}
+
+/// An operator that comes after the operand in a [`Precedence`] expression, like `x?`.
+pub trait PostfixOperator: Rule {
+    fn precedence(&self) -> u32;
+}
+
+impl InfixOperator for Reject {
+    fn precedence(&self) -> u32 {
+        match *self {}
+    }
+}
+
+impl PrefixOperator for Reject {
+    fn precedence(&self) -> u32 {
+        match *self {}
+    }
+}
+
+impl PostfixOperator for Reject {
+    fn precedence(&self) -> u32 {
+        match *self {}
+    }
+}
+
+type PrecedenceOperand<Atom, Prefix, Postfix> = (Vec<Prefix>, Atom, Vec<Postfix>);
+
+enum PrecedenceItem<Atom, Op, Prefix, Postfix> {
+    Atom(Atom),
+    Infix(Op),
+    Prefix(Prefix),
+    Postfix(Postfix),
+}
+
+/// An operator in a [`Precedence`] tree that's waiting for its (right) operand, along with the
+/// binding power that applied before it.
+enum PendingOperator<Tree, Op, Prefix> {
+    Prefix {
+        op: Prefix,
+        min_power: u64,
+    },
+    Binary {
+        lhs: Box<Tree>,
+        op: Op,
+        min_power: u64,
+    },
+}
+
+/// An expression tree of `Atom`s joined by operators, arranged according to each operator's
+/// precedence and associativity.
+#[derive(Debug)]
+pub enum Precedence<Atom, Op, Prefix = Reject, Postfix = Reject> {
+    Atom(Atom),
+    Prefix {
+        op: Prefix,
+        operand: Box<Self>,
+    },
+    Binary {
+        lhs: Box<Self>,
+        op: Op,
+        rhs: Box<Self>,
+    },
+    Postfix {
+        operand: Box<Self>,
+        op: Postfix,
+    },
+}
+
+impl<Atom, Op, Prefix, Postfix> Precedence<Atom, Op, Prefix, Postfix>
+where
+    Atom: Rule,
+    Op: InfixOperator,
+    Prefix: PrefixOperator,
+    Postfix: PostfixOperator,
+{
+    /// Builds a tree from `items`.
+    ///
+    /// Operators whose operand is still being built are kept on an explicit stack rather than
+    /// by recursing, since there can be as many of them as there are operators in the source.
+    fn climb(items: impl IntoIterator<Item = PrecedenceItem<Atom, Op, Prefix, Postfix>>) -> Self {
+        // Each precedence level gets two binding powers so associativity can break ties.
+        let power = |precedence: u32| u64::from(precedence) * 2;
+
+        let mut items = items.into_iter().peekable();
+        let mut pending = Vec::<PendingOperator<Self, Op, Prefix>>::new();
+        // operators bind to the current operand if they're at least as tight as this
+        let mut min_power = 0;
+
+        'operand: loop {
+            let mut lhs = loop {
+                match items.next() {
+                    Some(PrecedenceItem::Prefix(op)) => {
+                        let power = power(op.precedence()) + 1;
+                        pending.push(PendingOperator::Prefix { op, min_power });
+                        min_power = power;
+                    }
+                    Some(PrecedenceItem::Atom(atom)) => break Self::Atom(atom),
+                    _ => unreachable!("every operand has an atom"),
+                }
+            };
+
+            loop {
+                match items.peek() {
+                    Some(PrecedenceItem::Postfix(op)) if power(op.precedence()) >= min_power => {
+                        let Some(PrecedenceItem::Postfix(op)) = items.next() else {
+                            unreachable!()
+                        };
+                        lhs = Self::Postfix {
+                            operand: Box::new(lhs),
+                            op,
+                        };
+                        continue;
+                    }
+                    Some(PrecedenceItem::Infix(op)) => {
+                        let (left_power, right_power) = match op.associativity() {
+                            Associativity::Left => {
+                                (power(op.precedence()), power(op.precedence()) + 1)
+                            }
+                            Associativity::Right => {
+                                (power(op.precedence()) + 1, power(op.precedence()))
+                            }
+                        };
+
+                        if left_power >= min_power {
+                            let Some(PrecedenceItem::Infix(op)) = items.next() else {
+                                unreachable!()
+                            };
+                            pending.push(PendingOperator::Binary {
+                                lhs: Box::new(lhs),
+                                op,
+                                min_power,
+                            });
+                            min_power = right_power;
+                            continue 'operand;
+                        }
+                    }
+                    _ => {}
+                }
+
+                // nothing more binds to `lhs`, so it's the operand of the innermost pending operator
+                lhs = match pending.pop() {
+                    None => return lhs,
+                    Some(PendingOperator::Prefix {
+                        op,
+                        min_power: outer,
+                    }) => {
+                        min_power = outer;
+                        Self::Prefix {
+                            op,
+                            operand: Box::new(lhs),
+                        }
+                    }
+                    Some(PendingOperator::Binary {
+                        lhs: binary_lhs,
+                        op,
+                        min_power: outer,
+                    }) => {
+                        min_power = outer;
+                        Self::Binary {
+                            lhs: binary_lhs,
+                            op,
+                            rhs: Box::new(lhs),
+                        }
+                    }
+                };
+            }
+        }
+    }
+}
+
+impl<Atom, Op, Prefix, Postfix> TransformRule for Precedence<Atom, Op, Prefix, Postfix>
+where
+    Atom: Rule,
+    Op: InfixOperator,
+    Prefix: PrefixOperator,
+    Postfix: PostfixOperator,
+{
+    type Inner = (
+        PrecedenceOperand<Atom, Prefix, Postfix>,
+        Vec<(Op, PrecedenceOperand<Atom, Prefix, Postfix>)>,
+    );
+
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Atom(atom) => atom.print_tree(cx, f),
+            Self::Prefix { op, operand } => cx.debug_tuple("Prefix", f, [op as _, operand as _]),
+            Self::Binary { lhs, op, rhs } => {
+                cx.debug_tuple("Binary", f, [lhs as _, op as _, rhs as _])
+            }
+            Self::Postfix { operand, op } => cx.debug_tuple("Postfix", f, [operand as _, op as _]),
+        }
+    }
+
+    fn from_inner((first, rest): Self::Inner) -> Self {
+        let mut items = Vec::new();
+        let push_operand =
+            |items: &mut Vec<_>, (prefixes, atom, postfixes): PrecedenceOperand<_, _, _>| {
+                items.extend(prefixes.into_iter().map(PrecedenceItem::Prefix));
+                items.push(PrecedenceItem::Atom(atom));
+                items.extend(postfixes.into_iter().map(PrecedenceItem::Postfix));
+            };
+
+        push_operand(&mut items, first);
+        for (op, operand) in rest {
+            items.push(PrecedenceItem::Infix(op));
+            push_operand(&mut items, operand);
+        }
+
+        Self::climb(items)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NotParse<Invalid, Valid> {
     _invalid: PhantomData<Invalid>,
@@ -1544,11 +2663,14 @@ impl<Invalid: Rule, Valid: Rule> Rule for NotParse<Invalid, Valid> {
     where
         Self: Sized,
     {
-        let Err(_) = cx.isolated_parse::<(Invalid, Accept)>(None, default()) else {
+        let Err(err) = cx.isolated_parse::<(Invalid, Accept)>(None, default()) else {
             return Err(RuleParseFailed {
                 location: cx.location(),
             });
         };
+        if cx.is_aborted() {
+            return Err(err);
+        }
 
         Valid::pre_parse(cx, state, next)
     }
@@ -1557,11 +2679,14 @@ impl<Invalid: Rule, Valid: Rule> Rule for NotParse<Invalid, Valid> {
     where
         Self: Sized,
     {
-        let Err(_) = cx.isolated_parse::<(Invalid, Accept)>(None, default()) else {
+        let Err(err) = cx.isolated_parse::<(Invalid, Accept)>(None, default()) else {
             return Err(RuleParseFailed {
                 location: cx.location(),
             });
         };
+        if cx.is_aborted() {
+            return Err(err);
+        }
 
         Ok(Self {
             value: Valid::parse(cx, next)?,
@@ -1570,33 +2695,379 @@ impl<Invalid: Rule, Valid: Rule> Rule for NotParse<Invalid, Valid> {
     }
 }
 
-pub fn extract_actual<'src>(src: &'src str, start: usize) -> &'src str {
+/// Placeholder for input that failed to parse and was skipped by [`Recover`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErrorNode {
+    pub range: LocationRange,
+}
+
+impl Rule for ErrorNode {
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        if cx.is_debug() {
+            write!(f, "Error({:?})", self.range.slice(cx.src()))
+        } else {
+            f.write_str("<error>")
+        }
+    }
+
+    fn pre_parse<Cx: CxType>(
+        _: ParseContext<Cx>,
+        state: PreParseState,
+        _: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        Err(RuleParseFailed {
+            location: state.start,
+        })
+    }
+
+    fn parse<Cx: CxType>(cx: ParseContext<Cx>, _: &RuleType<Cx>) -> RuleParseResult<Self> {
+        Err(RuleParseFailed {
+            location: cx.location(),
+        })
+    }
+
+    fn matches_empty() -> bool {
+        false
+    }
+}
+
+/// Parses `T`, or on failure records the error and skips ahead so parsing can continue.
+///
+/// Input is skipped up to and including the next `Sync`, or up to but excluding the next `Stop`,
+/// whichever comes first. At least one character must be skipped for recovery to succeed, so a
+/// `Stop` at the start of the failed input (e.g. the closing brace of a block) fails as usual.
+///
+/// Since recovery is always possible when there's input left, `Recover` should be the last
+/// alternative in an `Either` or enum.
+pub struct Recover<T, Sync, Stop = Reject> {
+    pub value: Result<T, ErrorNode>,
+    _sync: PhantomData<(Sync, Stop)>,
+}
+
+impl<T, Sync, Stop> Recover<T, Sync, Stop> {
+    pub fn new(value: Result<T, ErrorNode>) -> Self {
+        Self {
+            value,
+            _sync: PhantomData,
+        }
+    }
+
+    pub fn ok(&self) -> Option<&T> {
+        self.value.as_ref().ok()
+    }
+}
+
+impl<T: Debug, Sync, Stop> Debug for Recover<T, Sync, Stop> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Ok(value) => value.fmt(f),
+            Err(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<T: Rule, Sync: Rule, Stop: Rule> Recover<T, Sync, Stop> {
+    /// Finds where recovery from a failure at `failed` would resume parsing.
+    fn recovery_end<Cx: CxType>(
+        cx: &mut ParseContext<Cx>,
+        start: Location,
+        failed: Location,
+    ) -> Location {
+        let src = cx.src();
+        let stop_at = |cx: &mut ParseContext<Cx>, location| {
+            cx.isolated_parse::<Silent<Discard<Stop>>>(location, default())
+                .is_ok()
+        };
+
+        if stop_at(cx, start) {
+            return start;
+        }
+
+        let mut location = failed.max(start);
+
+        loop {
+            if stop_at(cx, location) {
+                return location;
+            }
+
+            if let Ok(end) = cx.isolated_parse::<Silent<Discard<Sync>>>(location, default()) {
+                return end;
+            }
+
+            match src.get(location.position..).and_then(|s| s.chars().next()) {
+                Some(c) => location += c.len_utf8(),
+                None => return location,
+            }
+        }
+    }
+}
+
+impl<T: Rule, Sync: Rule, Stop: Rule> Rule for Recover<T, Sync, Stop> {
+    fn print_name(f: &mut Formatter) -> fmt::Result {
+        f.write_str("Recover(")?;
+        T::print_name(f)?;
+        f.write_str(", ")?;
+        Sync::print_name(f)?;
+        f.write_str(")")
+    }
+
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        match &self.value {
+            Ok(value) => value.print_tree(cx, f),
+            Err(error) => error.print_tree(cx, f),
+        }
+    }
+
+    fn print_visibility(&self, cx: &PrintContext) -> PrintVisibility {
+        match &self.value {
+            Ok(value) => value.print_visibility(cx),
+            Err(_) => PrintVisibility::Always,
+        }
+    }
+
+    fn pre_parse<Cx: CxType>(
+        mut cx: ParseContext<Cx>,
+        state: PreParseState,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<()> {
+        let err = match T::pre_parse(cx.by_ref(), state, next) {
+            Ok(()) => return Ok(()),
+            Err(err) if cx.is_aborted() => return Err(err),
+            Err(err) => err,
+        };
+
+        if Self::recovery_end(&mut cx, state.start, err.location) > state.start {
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    fn parse<Cx: CxType>(mut cx: ParseContext<Cx>, next: &RuleType<Cx>) -> RuleParseResult<Self> {
+        let src = cx.src();
+        let start = cx.location();
+        let mut location = start;
+        let mut look_ahead = cx.look_ahead().clone();
+        let mut error = ParseError {
+            location: start,
+            ..default()
+        };
+        let mut diagnostics = Vec::new();
+        let mark = cx.token_mark();
+
+        let result: RuleParseResult<T> = try_run(|| {
+            let mut cx = cx.by_ref().update(ParseContextUpdate {
+                location: Some(&mut location),
+                look_ahead: Some(&mut look_ahead),
+                error: Some(&mut error),
+                diagnostics: Some(&mut diagnostics),
+                ..default()
+            });
+            let value = T::parse(cx.by_ref(), next)?;
+
+            // also treat it as a failure if whatever follows can't be parsed, so the error is
+            // reported and skipped here rather than further up the tree.
+            let end = cx.location();
+            next.pre_parse(
+                cx.discarding(),
+                PreParseState {
+                    start: end,
+                    end: Location {
+                        position: src.len(),
+                    },
+                    dist: 0,
+                },
+            )?;
+
+            Ok(value)
+        });
+
+        if let Ok(value) = result {
+            *cx.look_ahead_mut() = look_ahead;
+            cx.set_location(location);
+            cx.error_mut().merge(&error);
+            diagnostics.into_iter().for_each(|d| cx.push_diagnostic(d));
+            return Ok(Self::new(Ok(value)));
+        }
+
+        if let Err(err) = &result {
+            if cx.is_aborted() {
+                return Err(RuleParseFailed {
+                    location: err.location,
+                });
+            }
+        }
+
+        cx.rollback_tokens(mark);
+        let end = Self::recovery_end(&mut cx, start, error.location);
+
+        if end <= start {
+            cx.error_mut().merge(&error);
+            return Err(RuleParseFailed {
+                location: error.location,
+            });
+        }
+
+        cx.push_diagnostic(error);
+        *cx.look_ahead_mut() = cx.look_ahead().cleared();
+        cx.set_location(end);
+        cx.extend_span(LocationRange { start, end });
+
+        Ok(Self::new(Err(ErrorNode {
+            range: LocationRange { start, end },
+        })))
+    }
+
+    fn matches_empty() -> bool {
+        T::matches_empty()
+    }
+}
+
+pub fn extract_actual(src: &str, start: usize) -> &str {
     if start >= src.len() {
         return "<end-of-file>";
     }
 
     crate::_lazy_regex! {
-        static ref PSEUDO_TOKEN => r"\A.+?\b|.";
+        static ref PSEUDO_TOKEN => r"\A(?:.+?\b|.)";
     }
 
     const MAX_LEN: usize = 32;
 
-    let len = PSEUDO_TOKEN
-        .find(&src[start..])
-        .map(|m| m.end().min(MAX_LEN))
-        .unwrap_or(1);
+    let src = &src[start..];
+    let mut len = match PSEUDO_TOKEN.find(src) {
+        Some(m) => m.end().min(MAX_LEN),
+        // only a line break can fail to match
+        None => src.chars().next().map_or(0, char::len_utf8),
+    };
 
-    &src[start..start + len]
+    while !src.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    &src[..len]
 }
 
 pub fn parse_tree<'src, T: Rule, const N: usize>(src: &'src str) -> Result<T, ParseError<'src>> {
-    match SizedParseContext::<N>::new_with(src, move |cx| {
-        <(T, Token<Eof>)>::parse(cx, &mut default())
-    }) {
-        (Ok((value, _)), _) => Ok(value),
-        (Err(_), mut err) => {
-            err.actual = extract_actual(src, err.location.position);
-            Err(err)
+    ParseOptions::new().parse_tree::<T, N>(src)
+}
+
+/// Parses `src`, continuing past errors that are handled by [`Recover`] rules.
+///
+/// Returns the tree, if the input as a whole could be parsed, along with every error encountered
+/// in the order they were found.
+pub fn parse_tree_recover<'src, T: Rule, const N: usize>(
+    src: &'src str,
+) -> (Option<T>, Vec<ParseError<'src>>) {
+    ParseOptions::new().parse_tree_recover::<T, N>(src)
+}
+
+/// Like [`parse_tree`], but with the lookahead chosen at runtime rather than compiled in.
+///
+/// This is somewhat slower, but lets the same grammar run with any lookahead without
+/// being compiled for each.
+pub fn parse_tree_dyn<T: Rule>(src: &str, look_ahead: usize) -> Result<T, ParseError<'_>> {
+    ParseOptions::new()
+        .look_ahead(look_ahead)
+        .parse_tree_dyn::<T>(src)
+}
+
+/// Like [`parse_tree`], but keeping track of the trivia between tokens, so the source can be
+/// reproduced from the tree.
+pub fn parse_cst<'src, T: Rule, const N: usize>(
+    src: &'src str,
+) -> Result<Cst<T>, ParseError<'src>> {
+    ParseOptions::new().parse_cst::<T, N>(src)
+}
+
+impl ParseOptions<'_> {
+    /// Like [`parse_tree`], but with these options.
+    pub fn parse_tree<'src, T: Rule, const N: usize>(
+        &self,
+        src: &'src str,
+    ) -> Result<T, ParseError<'src>> {
+        Self::first_error(self.parse_tree_recover::<T, N>(src))
+    }
+
+    /// Like [`parse_tree_recover`], but with these options.
+    pub fn parse_tree_recover<'src, T: Rule, const N: usize>(
+        &self,
+        src: &'src str,
+    ) -> (Option<T>, Vec<ParseError<'src>>) {
+        self.parse_tree_with::<T, [Option<AnyToken>; N]>(src)
+    }
+
+    /// Like [`parse_tree_dyn`], but with these options, including the lookahead set by
+    /// [`look_ahead`](Self::look_ahead).
+    pub fn parse_tree_dyn<'src, T: Rule>(&self, src: &'src str) -> Result<T, ParseError<'src>> {
+        Self::first_error(self.parse_tree_recover_dyn::<T>(src))
+    }
+
+    /// Like [`parse_tree_recover`], but with the lookahead set by [`look_ahead`](Self::look_ahead).
+    pub fn parse_tree_recover_dyn<'src, T: Rule>(
+        &self,
+        src: &'src str,
+    ) -> (Option<T>, Vec<ParseError<'src>>) {
+        self.parse_tree_with::<T, Vec<Option<AnyToken>>>(src)
+    }
+
+    /// Like [`parse_cst`], but with these options.
+    pub fn parse_cst<'src, T: Rule, const N: usize>(
+        &self,
+        src: &'src str,
+    ) -> Result<Cst<T>, ParseError<'src>> {
+        let (value, diagnostics) = self
+            .clone()
+            .lossless(true)
+            .parse_with::<T, [Option<AnyToken>; N]>(src);
+        let value = value.map(|(ast, tokens)| Cst::new(ast, src, tokens));
+        Self::first_error((value, diagnostics))
+    }
+
+    fn first_error<'src, T>(
+        (value, mut diagnostics): (Option<T>, Vec<ParseError<'src>>),
+    ) -> Result<T, ParseError<'src>> {
+        match value {
+            Some(value) if diagnostics.is_empty() => Ok(value),
+            _ => Err(diagnostics.remove(0)),
         }
     }
+
+    fn parse_tree_with<'src, T: Rule, A: TokenBufData>(
+        &self,
+        src: &'src str,
+    ) -> (Option<T>, Vec<ParseError<'src>>) {
+        let (value, diagnostics) = self.parse_with::<T, A>(src);
+        (value.map(|(value, _)| value), diagnostics)
+    }
+
+    /// Parses `src`, returning the tree along with the tokens recorded for it, if any.
+    fn parse_with<'src, T: Rule, A: TokenBufData>(
+        &self,
+        src: &'src str,
+    ) -> (Option<(T, Vec<AnyToken>)>, Vec<ParseError<'src>>) {
+        let (result, err, mut diagnostics) =
+            RootParseContext::<A>::new_with(src, self, move |mut cx| {
+                let (value, _) = <(T, Token<Eof>)>::parse(cx.by_ref(), default())?;
+                Ok((value, cx.take_recorded_tokens()))
+            });
+
+        let value = match result {
+            Ok(value) => Some(value),
+            Err(_) => {
+                diagnostics.push(err);
+                None
+            }
+        };
+
+        for err in &mut diagnostics {
+            err.src = Some(src);
+            err.actual = match err.kind {
+                ParseErrorKind::Invalid { range, .. } => range.slice(src),
+                _ => extract_actual(src, err.location.position),
+            };
+        }
+
+        (value, diagnostics)
+    }
 }
@@ -126,6 +126,28 @@ impl<'src> PrintContext<'src> {
         }
     }
 
+    /// Like [`debug_rule`](Self::debug_rule), but for the fields of a rule defined with
+    /// [`define_rule!`](crate::define_rule), which aren't all rules themselves.
+    #[doc(hidden)]
+    pub fn debug_fields<'item>(
+        &self,
+        f: &mut Formatter,
+        fields: impl IntoIterator<Item = Field<'item>>,
+    ) -> fmt::Result {
+        let fields = fields
+            .into_iter()
+            .filter(|field| field.print_visibility(self).should_print(self));
+        match iter_special_case(fields) {
+            IterSpecialCase::Zero => f.write_str("{}"),
+            IterSpecialCase::One(field) => field.print_tree(self, f),
+            IterSpecialCase::Many(fields) => fields
+                .fold(&mut f.debug_set(), |d, field| {
+                    d.entry(&DebugFn(move |f| field.print_tree(self, f)))
+                })
+                .finish(),
+        }
+    }
+
     pub fn is_debug(&self) -> bool {
         self.debug
     }
@@ -153,3 +175,56 @@ impl PrintVisibility {
         }
     }
 }
+
+/// A field of a rule defined with [`define_rule!`](crate::define_rule), which is printed as a
+/// rule if it is one, or otherwise as a value, e.g. one produced by
+/// [`with_source`](super::transform::with_source).
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub enum Field<'a> {
+    Rule(&'a dyn Rule),
+    Value(&'a dyn Debug),
+}
+
+impl Field<'_> {
+    fn print_visibility(&self, cx: &PrintContext) -> PrintVisibility {
+        match self {
+            Field::Rule(rule) => rule.print_visibility(cx),
+            Field::Value(_) => PrintVisibility::Always,
+        }
+    }
+
+    fn print_tree(&self, cx: &PrintContext, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Field::Rule(rule) => rule.print_tree(cx, f),
+            Field::Value(value) => value.fmt(f),
+        }
+    }
+}
+
+/// Picks how to print a field: calling `(&FieldRef(&field)).field()` with [`RuleField`] and
+/// [`ValueField`] in scope prefers the former, which only applies to rules.
+#[doc(hidden)]
+pub struct FieldRef<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub trait RuleField<'a> {
+    fn field(&self) -> Field<'a>;
+}
+
+impl<'a, T: Rule> RuleField<'a> for FieldRef<'a, T> {
+    fn field(&self) -> Field<'a> {
+        Field::Rule(self.0)
+    }
+}
+
+#[doc(hidden)]
+pub trait ValueField<'a> {
+    fn field(&self) -> Field<'a>;
+}
+
+impl<'a, T: Debug> ValueField<'a> for &FieldRef<'a, T> {
+    fn field(&self) -> Field<'a> {
+        Field::Value(self.0)
+    }
+}
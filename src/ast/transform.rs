@@ -5,7 +5,8 @@ use core::marker::PhantomData;
 use crate::{internal_prelude::*, Rule};
 
 use super::{
-    CompoundToken, DelimitedList, Discard, DualParse, Empty, Ignore, NotParse, TransformList,
+    CompoundToken, DelimitedList, Discard, DualParse, Empty, Ignore, NotParse, SourceTransformed,
+    Spanned, TransformList, Transformed, TryTransformed,
 };
 
 pub trait TransformInto<Out> {
@@ -13,6 +14,118 @@ pub trait TransformInto<Out> {
     fn transform(input: Self::Input) -> Out;
 }
 
+/// A transform that needs the source its input was parsed from, e.g. to turn the range of a token
+/// into a `String`, or to unescape a string literal. It's applied with [`with_source`].
+///
+/// ```
+/// # use rs_typed_parser::ast::{transform::TransformWithSource, Token};
+/// rs_typed_parser::define_token!(
+///     #[pattern(regex = "[a-z]+")]
+///     pub struct Ident;
+/// );
+///
+/// pub struct ident_name;
+///
+/// impl TransformWithSource<String> for ident_name {
+///     type Input = Token<Ident>;
+///
+///     fn transform(input: Self::Input, src: &str) -> String {
+///         input.range.slice(src).into()
+///     }
+/// }
+///
+/// rs_typed_parser::define_rule!(
+///     pub struct Var {
+///         #[transform(with_source<ident_name>)]
+///         name: String,
+///     }
+/// );
+///
+/// let var = rs_typed_parser::parse_tree::<Var, 1>("x").unwrap();
+/// assert_eq!(var.name, "x");
+/// ```
+pub trait TransformWithSource<Out> {
+    type Input;
+    fn transform(input: Self::Input, src: &str) -> Out;
+}
+
+/// Applies the [`TransformWithSource`] `X`.
+pub struct with_source<X> {
+    _x: PhantomData<X>,
+}
+
+impl<In: Rule, Out: 'static, X: TransformWithSource<Out, Input = In> + 'static> TransformInto<Out>
+    for with_source<X>
+{
+    type Input = SourceTransformed<Out, X>;
+
+    fn transform(input: Self::Input) -> Out {
+        input.value
+    }
+}
+
+/// A transform that can reject its input, e.g. a number that's out of range, with a message to
+/// report. It's applied with [`try_transform`], or `#[transform(try X)]` in
+/// [`define_rule`](crate::define_rule).
+///
+/// ```
+/// # use rs_typed_parser::{ast::{transform::TryTransformInto, Token}, parse::ParseErrorKind};
+/// rs_typed_parser::define_token!(
+///     #[pattern(regex = "[0-9]+")]
+///     pub struct Digits;
+/// );
+///
+/// pub struct byte;
+///
+/// impl TryTransformInto<u8> for byte {
+///     type Input = Token<Digits>;
+///
+///     fn try_transform(input: Self::Input, src: &str) -> Result<u8, String> {
+///         let text = input.range.slice(src);
+///         text.parse().map_err(|_| format!("{text} is out of range for a byte"))
+///     }
+/// }
+///
+/// rs_typed_parser::define_rule!(
+///     pub struct Byte {
+///         #[transform(try byte)]
+///         value: u8,
+///     }
+/// );
+///
+/// assert_eq!(rs_typed_parser::parse_tree::<Byte, 1>("42").unwrap().value, 42);
+///
+/// let err = rs_typed_parser::parse_tree::<Byte, 1>("256").unwrap_err();
+/// let ParseErrorKind::Invalid { message, .. } = err.kind else {
+///     panic!("expected an invalid value");
+/// };
+/// assert_eq!(message, "256 is out of range for a byte");
+/// ```
+pub trait TryTransformInto<Out> {
+    type Input;
+    fn try_transform(input: Self::Input, src: &str) -> Result<Out, String>;
+}
+
+/// Applies the [`TryTransformInto`] `X`. See [`TryTransformed`] for what happens when it fails,
+/// and when `BACKTRACK` is needed.
+pub struct try_transform<X, const BACKTRACK: bool = false> {
+    _x: PhantomData<X>,
+}
+
+impl<
+        In: Rule,
+        Out: 'static,
+        X: TryTransformInto<Out, Input = In> + 'static,
+        const BACKTRACK: bool,
+    > TransformInto<Out> for try_transform<X, BACKTRACK>
+{
+    type Input = TryTransformed<Out, X, BACKTRACK>;
+
+    fn transform(input: Self::Input) -> Out {
+        input.value
+    }
+}
+
 #[non_exhaustive]
 pub struct identity {}
 
@@ -52,12 +165,19 @@ impl<T: Rule, Delim: Rule, const TRAIL: bool> TransformInto<Vec<T>> for delimite
     }
 }
 
-impl<T: Rule, X: TransformInto<T>, Delim: Rule, const TRAIL: bool>
-    TransformInto<TransformList<T, X, Delim>> for delimited<Delim, TRAIL>
+impl<
+        T: Rule,
+        X: TransformInto<T>,
+        Delim: Rule,
+        const TRAIL: bool,
+        const MIN: usize,
+        const MAX: usize,
+    > TransformInto<TransformList<T, X, Delim, false, false, MIN, MAX>>
+    for delimited<Delim, TRAIL>
 {
-    type Input = TransformList<T, X, Delim, TRAIL>;
+    type Input = TransformList<T, X, Delim, TRAIL, false, MIN, MAX>;
 
-    fn transform(input: Self::Input) -> TransformList<T, X, Delim> {
+    fn transform(input: Self::Input) -> TransformList<T, X, Delim, false, false, MIN, MAX> {
         TransformList::new(input.items)
     }
 }
@@ -119,28 +239,38 @@ pub struct for_each<X> {
 }
 
 impl<In: Rule, Out, X: TransformInto<Out, Input = In>> TransformInto<Vec<Out>> for for_each<X> {
-    type Input = TransformList<Out, X>;
+    type Input = TransformList<In, identity>;
 
     fn transform(input: Self::Input) -> Vec<Out> {
-        input.items
+        input.items.into_iter().map(X::transform).collect()
     }
 }
 
 impl<
         In: Rule,
         Out,
-        In1: Rule,
+        In1,
         X: TransformInto<In1, Input = In>,
         X1: TransformInto<Out, Input = In1>,
         Delim: Rule,
         const TRAIL: bool,
         const PREFER_SHORT: bool,
-    > TransformInto<TransformList<Out, X1, Delim, TRAIL, PREFER_SHORT>> for for_each<X>
+        const MIN: usize,
+        const MAX: usize,
+    > TransformInto<TransformList<Out, X1, Delim, TRAIL, PREFER_SHORT, MIN, MAX>> for for_each<X>
 {
-    type Input = TransformList<Out, compose<X, X1>, Delim, TRAIL, PREFER_SHORT>;
-
-    fn transform(input: Self::Input) -> TransformList<Out, X1, Delim, TRAIL, PREFER_SHORT> {
-        TransformList::new(input.items)
+    type Input = TransformList<In, identity, Delim, TRAIL, PREFER_SHORT, MIN, MAX>;
+
+    fn transform(
+        input: Self::Input,
+    ) -> TransformList<Out, X1, Delim, TRAIL, PREFER_SHORT, MIN, MAX> {
+        TransformList::new(
+            input
+                .items
+                .into_iter()
+                .map(|item| X1::transform(X::transform(item)))
+                .collect(),
+        )
     }
 }
 
@@ -162,16 +292,36 @@ impl<
         const TRAIL: bool,
         const PREFER_SHORT: bool,
         const PREFER_SHORT1: bool,
-    > TransformInto<TransformList<T, X, Delim, TRAIL, PREFER_SHORT1>>
+        const MIN: usize,
+        const MAX: usize,
+    > TransformInto<TransformList<T, X, Delim, TRAIL, PREFER_SHORT1, MIN, MAX>>
     for prefer_short<PREFER_SHORT>
 {
-    type Input = TransformList<T, X, Delim, TRAIL, PREFER_SHORT>;
+    type Input = TransformList<T, X, Delim, TRAIL, PREFER_SHORT, MIN, MAX>;
 
-    fn transform(input: Self::Input) -> TransformList<T, X, Delim, TRAIL, PREFER_SHORT1> {
+    fn transform(input: Self::Input) -> TransformList<T, X, Delim, TRAIL, PREFER_SHORT1, MIN, MAX> {
         TransformList::new(input.items)
     }
 }
 
+/// Applies `X` and keeps the range of what it was applied to, into a [`Spanned`].
+pub struct spanned<X = identity> {
+    _x: PhantomData<X>,
+}
+
+impl<In: Rule, Out: 'static, X: TransformInto<Out, Input = In> + 'static>
+    TransformInto<Spanned<Out>> for spanned<X>
+{
+    type Input = Spanned<Transformed<Out, X>>;
+
+    fn transform(input: Self::Input) -> Spanned<Out> {
+        Spanned {
+            value: input.value.value,
+            span: input.span,
+        }
+    }
+}
+
 #[non_exhaustive]
 pub struct compound_token {}
 
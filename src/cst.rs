@@ -0,0 +1,168 @@
+//! Lossless trees, which keep track of the text between the tokens of a tree so the source can be
+//! reproduced exactly, e.g. for formatters and refactoring tools.
+//!
+//! ```
+//! # use rs_typed_parser::{ast::Ignore, ParseOptions};
+//! rs_typed_parser::define_token!(
+//!     #[pattern(regex = "[0-9]+")]
+//!     pub struct Digits;
+//!     #[pattern(regex = r"\s+")]
+//!     pub struct Space;
+//! );
+//!
+//! let src = "  42 \n";
+//! let cst = ParseOptions::new()
+//!     .trivia::<Ignore<Space>>()
+//!     .parse_cst::<Digits, 1>(src)
+//!     .unwrap();
+//! assert_eq!(cst.tokens[0].leading.slice(src), "  ");
+//! assert_eq!(cst.to_source(src), src);
+//! ```
+
+use core::fmt::{self, Write};
+
+use crate::{
+    internal_prelude::*,
+    parse::{Location, LocationRange},
+    token::AnyToken,
+};
+
+/// A token of a tree along with the trivia around it.
+///
+/// Trivia is whatever lies between tokens without being part of the tree, like skipped whitespace
+/// and comments, and tokens that were discarded. The trivia after a token, up to and including
+/// the end of its line, is trailing trivia, and the rest is leading trivia of the next token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CstToken {
+    pub token: AnyToken,
+    pub leading: LocationRange,
+    pub trailing: LocationRange,
+}
+
+impl CstToken {
+    /// The range of the token along with its trivia.
+    pub fn full_range(&self) -> LocationRange {
+        LocationRange {
+            start: self.leading.start,
+            end: self.trailing.end,
+        }
+    }
+}
+
+/// The tokens of a node of a tree, found with [`Cst::node`], whose trivia is the leading trivia
+/// of its first token and the trailing trivia of its last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CstNode<'a> {
+    pub tokens: &'a [CstToken],
+}
+
+impl CstNode<'_> {
+    pub fn leading(&self) -> LocationRange {
+        self.tokens[0].leading
+    }
+
+    pub fn trailing(&self) -> LocationRange {
+        self.tokens[self.tokens.len() - 1].trailing
+    }
+
+    /// The range of the node along with its trivia.
+    pub fn full_range(&self) -> LocationRange {
+        LocationRange {
+            start: self.leading().start,
+            end: self.trailing().end,
+        }
+    }
+}
+
+/// A tree along with every token in it, in order, that together cover the whole source.
+///
+/// The last token is always [`Eof`](crate::token::Eof), whose leading trivia is whatever follows
+/// the last line of the tree.
+#[derive(Debug, Clone)]
+pub struct Cst<T> {
+    pub ast: T,
+    pub tokens: Vec<CstToken>,
+}
+
+impl<T> Cst<T> {
+    pub(crate) fn new(ast: T, src: &str, recorded: Vec<AnyToken>) -> Self {
+        let mut tokens: Vec<CstToken> = Vec::with_capacity(recorded.len());
+        let mut end = Location::MIN;
+
+        for token in recorded {
+            if token.range.start < end {
+                continue;
+            }
+
+            let gap = LocationRange {
+                start: end,
+                end: token.range.start,
+            };
+            let split = match tokens.last_mut() {
+                Some(prev) => {
+                    let slice = gap.slice(src);
+                    prev.trailing.end = slice.find('\n').map_or(gap.end, |i| gap.start + (i + 1));
+                    prev.trailing.end
+                }
+                None => gap.start,
+            };
+
+            tokens.push(CstToken {
+                token,
+                leading: LocationRange {
+                    start: split,
+                    end: token.range.start,
+                },
+                trailing: LocationRange {
+                    start: token.range.end,
+                    end: token.range.end,
+                },
+            });
+            end = token.range.end;
+        }
+
+        Self { ast, tokens }
+    }
+
+    /// Returns the token of the tree at `range`, e.g. that of a [`Token`](crate::ast::Token) in
+    /// the tree, to find the trivia around it.
+    pub fn token(&self, range: LocationRange) -> Option<&CstToken> {
+        let i = self
+            .tokens
+            .partition_point(|token| token.token.range.start < range.start);
+        self.tokens[i..]
+            .iter()
+            .take_while(|token| token.token.range.start == range.start)
+            .find(|token| token.token.range == range)
+    }
+
+    /// Returns the tokens of the tree within `range`, e.g. the span of a
+    /// [`Spanned`](crate::ast::Spanned) node, to find the trivia around the node. Returns `None` if
+    /// there aren't any, e.g. because the node is empty or its tokens were discarded.
+    pub fn node(&self, range: LocationRange) -> Option<CstNode<'_>> {
+        let start = self
+            .tokens
+            .partition_point(|token| token.token.range.start < range.start);
+        let end = self
+            .tokens
+            .partition_point(|token| token.token.range.start < range.end);
+        let tokens = self
+            .tokens
+            .get(start..end)
+            .filter(|tokens| !tokens.is_empty())?;
+        Some(CstNode { tokens })
+    }
+
+    /// Writes the source back out from the tokens and their trivia.
+    pub fn write_source(&self, src: &str, f: &mut impl Write) -> fmt::Result {
+        self.tokens
+            .iter()
+            .try_for_each(|token| f.write_str(token.full_range().slice(src)))
+    }
+
+    pub fn to_source(&self, src: &str) -> String {
+        let mut out = String::with_capacity(src.len());
+        let _ = self.write_source(src, &mut out);
+        out
+    }
+}
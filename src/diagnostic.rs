@@ -0,0 +1,198 @@
+use core::fmt::{self, Display, Formatter};
+
+use crate::{
+    internal_prelude::*,
+    parse::{LineIndex, Location, ParseError, ParseErrorKind},
+};
+
+const RESET: &str = "\x1b[0m";
+const ERROR_STYLE: &str = "\x1b[1;31m";
+const GUTTER_STYLE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+
+/// Renders a [`ParseError`] along with the line of source it occurred on, if the error has its
+/// source.
+///
+/// ```text
+/// error: expected one of Comma, RBrace, found `+`
+///  --> 3:5
+///   |
+/// 3 |   b + +
+///   |       ^
+/// ```
+pub struct ErrorDisplay<'err, 'src> {
+    error: &'err ParseError<'src>,
+    index: Option<&'err LineIndex<'src>>,
+    color: bool,
+}
+
+impl<'err, 'src> ErrorDisplay<'err, 'src> {
+    pub fn new(error: &'err ParseError<'src>) -> Self {
+        Self {
+            error,
+            index: None,
+            color: false,
+        }
+    }
+
+    /// Enables or disables ANSI colour codes in the output.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Reuses an existing index of the error's source instead of building a new one.
+    pub fn with_index(mut self, index: &'err LineIndex<'src>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    fn style(&self, style: &'static str) -> &'static str {
+        if self.color {
+            style
+        } else {
+            ""
+        }
+    }
+
+    fn write_message(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.error.kind {
+            ParseErrorKind::Unexpected => self.write_unexpected(f),
+            ParseErrorKind::LeftRecursion { cycle } => {
+                f.write_str("left recursion in ")?;
+                for (i, name) in cycle.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" -> ")?;
+                    }
+                    f.write_str(name)?;
+                }
+                Ok(())
+            }
+            ParseErrorKind::EmptyRepetition => {
+                f.write_str("repeated item matched nothing, so it would repeat forever")
+            }
+            ParseErrorKind::NestingTooDeep { max_depth } => {
+                write!(f, "nesting too deep, exceeding the limit of {max_depth}")
+            }
+            ParseErrorKind::OutOfFuel => f.write_str("parse took too many steps"),
+            ParseErrorKind::Cancelled => f.write_str("parse was cancelled"),
+            ParseErrorKind::Invalid { message, .. } => f.write_str(message),
+        }
+    }
+
+    fn write_unexpected(&self, f: &mut Formatter) -> fmt::Result {
+        let mut names = Vec::<&str>::new();
+        for expected in self.error.expected() {
+            let name = expected.display_name();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        match *names {
+            [] => f.write_str("unexpected ")?,
+            [name] => write!(f, "expected {name}, found ")?,
+            [first, second] => write!(f, "expected {first} or {second}, found ")?,
+            [first, ref rest @ ..] => {
+                write!(f, "expected one of {first}")?;
+                for name in rest {
+                    write!(f, ", {name}")?;
+                }
+                f.write_str(", found ")?;
+            }
+        }
+
+        if self.at_eof() {
+            f.write_str("end-of-file")
+        } else {
+            write!(f, "`{}`", self.error.actual)
+        }
+    }
+
+    fn at_eof(&self) -> bool {
+        self.error
+            .src
+            .is_some_and(|src| self.error.location.position >= src.len())
+    }
+
+    fn write_snippet(&self, f: &mut Formatter, src: &str, index: &LineIndex) -> fmt::Result {
+        let (reset, error_style, gutter_style) = (
+            self.style(RESET),
+            self.style(ERROR_STYLE),
+            self.style(GUTTER_STYLE),
+        );
+
+        let location = self.error.location.min(Location {
+            position: src.len(),
+        });
+        let line_column = index.line_column(location);
+        let Some(line_range) = index.line_range(line_column.line) else {
+            return Ok(());
+        };
+        let line = &index.src()[line_range.start.position..line_range.end.position];
+        let prefix = &line[..(location.position - line_range.start.position).min(line.len())];
+
+        let underline_len = match self.at_eof() {
+            true => 1,
+            false => {
+                let rest = &line[prefix.len()..];
+                let len = self.error.actual.len().min(rest.len());
+                rest.get(..len).map_or(1, |s| s.chars().count().max(1))
+            }
+        };
+
+        let number = line_column.line.to_string();
+        let pad = " ".repeat(number.len());
+
+        writeln!(f, "{pad}{gutter_style}-->{reset} {line_column}")?;
+        writeln!(f, "{pad} {gutter_style}|{reset}")?;
+        writeln!(f, "{gutter_style}{number} |{reset} {line}")?;
+        write!(f, "{pad} {gutter_style}|{reset} ")?;
+
+        // keep tabs so the caret lines up with the source line
+        for c in prefix.chars() {
+            f.write_str(if c == '\t' { "\t" } else { " " })?;
+        }
+
+        write!(f, "{error_style}{}{reset}", "^".repeat(underline_len))
+    }
+}
+
+impl Display for ErrorDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (reset, error_style, bold) =
+            (self.style(RESET), self.style(ERROR_STYLE), self.style(BOLD));
+
+        write!(f, "{error_style}error{reset}{bold}: ")?;
+        self.write_message(f)?;
+        f.write_str(reset)?;
+
+        let Some(src) = self.error.src else {
+            return Ok(());
+        };
+
+        f.write_str("\n")?;
+
+        match self.index {
+            Some(index) => self.write_snippet(f, src, index),
+            None => self.write_snippet(f, src, &LineIndex::new(src)),
+        }
+    }
+}
+
+impl<'src> ParseError<'src> {
+    pub fn display(&self) -> ErrorDisplay<'_, 'src> {
+        ErrorDisplay::new(self)
+    }
+}
+
+/// Writes the message of the error on one line. Use [`display`](ParseError::display) to also show
+/// where it occurred.
+impl Display for ParseError<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.display().write_message(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError<'_> {}
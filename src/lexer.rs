@@ -0,0 +1,353 @@
+//! Splitting input into tokens on its own, without parsing it.
+//!
+//! ```
+//! # use rs_typed_parser::{lexer::Lexer, token::TokenType};
+//! rs_typed_parser::define_token!(
+//!     #[pattern(regex = "[a-z]+")]
+//!     pub struct Word;
+//!     #[pattern(regex = r"\s+")]
+//!     pub struct Space;
+//! );
+//!
+//! let lexer = Lexer::new([TokenType::of::<Word>()]).skip([TokenType::of::<Space>()]);
+//! let words = lexer.tokens("hello  world").map(|token| token.unwrap().range);
+//! assert_eq!(words.count(), 2);
+//! ```
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::{any::TypeId, fmt};
+
+use regex_automata::{
+    hybrid::dfa::{Cache, OverlappingState, DFA},
+    util::pool::Pool,
+    Anchored, Input, MatchKind,
+};
+
+use crate::{
+    internal_prelude::*,
+    parse::{Location, ParseError},
+    token::{AnyToken, TokenType},
+};
+
+/// Which token to produce when several match at the same location.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Conflict {
+    /// The longest match, or the first declared of those that are longest.
+    #[default]
+    LongestMatch,
+    /// The first declared token that matches.
+    FirstMatch,
+}
+
+/// A set of tokens to split input into.
+///
+/// Tokens are declared in order, with skipped tokens before the rest, which decides conflicts
+/// according to [`Conflict`]. Matches that are empty are ignored, since they wouldn't make
+/// progress.
+///
+/// The [patterns](crate::token::TokenDef::pattern) of the tokens are combined into a single
+/// automaton, which finds how far each of them matches in one pass over the input. That rules
+/// out the tokens whose patterns don't match, and bounds the length of the rest, since a token
+/// can't be longer than the longest match of its pattern. So only the tokens that could still be
+/// the longest are lexed individually, which is usually just one. Setting the lexer with
+/// [`ParseOptions::lexer`](crate::ParseOptions::lexer) lets a parse rule out tokens too.
+///
+/// The patterns only bound the tokens, rather than deciding them, because a token can still
+/// reject its match, e.g. a keyword that's followed by an identifier character.
+#[derive(Debug, Default, Clone)]
+pub struct Lexer {
+    /// Every token that can be lexed, and whether it's skipped.
+    tokens: Vec<(&'static TokenType, bool)>,
+    /// The index in `tokens` of each token.
+    indices: BTreeMap<TypeId, usize>,
+    conflict: Conflict,
+    /// The automaton matching every pattern, unless it couldn't be built from them.
+    automaton: Option<Automaton>,
+    /// The index in `automaton` of the pattern of each token, if it has one.
+    pattern_indices: Vec<Option<usize>>,
+}
+
+/// A lazy DFA matching the patterns of a [`Lexer`], with caches for the states it has built so
+/// far.
+#[derive(Clone)]
+struct Automaton {
+    dfa: Arc<DFA>,
+    caches: Arc<Pool<Cache, CacheFn>>,
+}
+
+type CacheFn = Box<dyn Fn() -> Cache + Send + Sync>;
+
+impl fmt::Debug for Automaton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Automaton(..)")
+    }
+}
+
+impl Automaton {
+    fn new(patterns: &[String]) -> Option<Self> {
+        let config = DFA::config()
+            .match_kind(MatchKind::All)
+            .unicode_word_boundary(true);
+        let dfa = Arc::new(DFA::builder().configure(config).build_many(patterns).ok()?);
+        let caches: CacheFn = Box::new({
+            let dfa = dfa.clone();
+            move || dfa.create_cache()
+        });
+        Some(Self {
+            dfa,
+            caches: Arc::new(Pool::new(caches)),
+        })
+    }
+
+    /// Finds the end of the longest match of each pattern at the start of `src`, or `None` if
+    /// the search gave up, e.g. on a Unicode word boundary in non-ASCII input.
+    fn longest_matches(&self, src: &str) -> Option<Vec<Option<usize>>> {
+        let mut ends = alloc::vec![None; self.dfa.pattern_len()];
+        let input = Input::new(src).anchored(Anchored::Yes);
+        let mut cache = self.caches.get();
+        let mut state = OverlappingState::start();
+        loop {
+            self.dfa
+                .try_search_overlapping_fwd(&mut cache, &input, &mut state)
+                .ok()?;
+            let Some(found) = state.get_match() else {
+                return Some(ends);
+            };
+            let end = &mut ends[found.pattern().as_usize()];
+            *end = (*end).max(Some(found.offset()));
+        }
+    }
+}
+
+/// How far the pattern of each token of a [`Lexer`] matches at some location.
+#[derive(Debug, Clone)]
+pub(crate) struct PatternMatches {
+    /// The end of the longest match of each pattern, relative to the location, or `None` if the
+    /// patterns couldn't be matched, in which case any token may match.
+    ends: Option<Vec<Option<usize>>>,
+}
+
+impl Lexer {
+    pub fn new(tokens: impl IntoIterator<Item = &'static TokenType>) -> Self {
+        let mut lexer = Self {
+            tokens: tokens.into_iter().map(|token| (token, false)).collect(),
+            ..Self::default()
+        };
+        lexer.compile();
+        lexer
+    }
+
+    /// Also lexes `tokens`, e.g. whitespace and comments, but leaves them out of the output.
+    pub fn skip(mut self, tokens: impl IntoIterator<Item = &'static TokenType>) -> Self {
+        let skipped = self.tokens.iter().filter(|(_, skip)| *skip).count();
+        self.tokens.splice(
+            skipped..skipped,
+            tokens.into_iter().map(|token| (token, true)),
+        );
+        self.compile();
+        self
+    }
+
+    fn compile(&mut self) {
+        let mut patterns = Vec::new();
+        self.pattern_indices = self
+            .tokens
+            .iter()
+            .map(|(token_type, _)| {
+                patterns.push(token_type.pattern()?);
+                Some(patterns.len() - 1)
+            })
+            .collect();
+        self.automaton = Automaton::new(&patterns);
+        self.indices.clear();
+        for (i, (token_type, _)) in self.tokens.iter().enumerate().rev() {
+            self.indices.insert(token_type.token_id(), i);
+        }
+    }
+
+    /// Whether `token_type` is one of the tokens of this lexer.
+    pub fn contains(&self, token_type: &TokenType) -> bool {
+        self.indices.contains_key(&token_type.token_id())
+    }
+
+    pub(crate) fn index_of(&self, token_type: &TokenType) -> Option<usize> {
+        self.indices.get(&token_type.token_id()).copied()
+    }
+
+    /// Finds how far the pattern of each token matches at `location`, for
+    /// [`max_len`](Self::max_len).
+    pub(crate) fn pattern_matches(&self, src: &str, location: Location) -> PatternMatches {
+        PatternMatches {
+            ends: self
+                .automaton
+                .as_ref()
+                .and_then(|automaton| automaton.longest_matches(&src[location.position..])),
+        }
+    }
+
+    /// The longest that the token at `index` can be according to `matches`, or `None` if it was
+    /// ruled out, so it only has to be lexed if it's `Some`.
+    pub(crate) fn max_len(&self, matches: &PatternMatches, index: usize) -> Option<usize> {
+        match (self.pattern_indices[index], &matches.ends) {
+            (Some(pattern), Some(ends)) => ends[pattern],
+            _ => Some(usize::MAX),
+        }
+    }
+
+    /// Lexes every token that matches at `location`, in the order they were declared, along with
+    /// whether each is skipped.
+    pub fn matches_at<'a>(
+        &'a self,
+        src: &'a str,
+        location: Location,
+    ) -> impl Iterator<Item = (AnyToken, bool)> + 'a {
+        let matches = self.pattern_matches(src, location);
+
+        self.tokens
+            .iter()
+            .enumerate()
+            .filter(move |&(i, _)| self.max_len(&matches, i).is_some())
+            .filter_map(move |(_, &(token_type, skip))| {
+                Some((token_type.try_lex(src, location)?, skip))
+            })
+    }
+
+    pub fn conflict(mut self, conflict: Conflict) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    /// Lexes the token at `location`, returning it and whether it's skipped.
+    pub fn lex_at(&self, src: &str, location: Location) -> Option<(AnyToken, bool)> {
+        let lex = |i: usize| {
+            let (token_type, skip) = self.tokens[i];
+            let token = token_type.try_lex(src, location)?;
+            (token.range.end > token.range.start).then_some((token, skip))
+        };
+
+        let matches = self.pattern_matches(src, location);
+        let mut candidates: Vec<_> = (0..self.tokens.len())
+            .filter_map(|i| Some((i, self.max_len(&matches, i)?)))
+            .collect();
+
+        if self.conflict == Conflict::FirstMatch {
+            return candidates.into_iter().find_map(|(i, _)| lex(i));
+        }
+
+        // the tokens that could be longest are lexed first, until none of the rest could be as
+        // long as the longest so far, or as long and declared before it
+        candidates.sort_by_key(|&(i, max_len)| (core::cmp::Reverse(max_len), i));
+        let mut longest: Option<(usize, usize, (AnyToken, bool))> = None;
+        for (i, max_len) in candidates {
+            if let Some((len, index, _)) = longest {
+                if max_len < len {
+                    break;
+                }
+                if max_len == len && i > index {
+                    continue;
+                }
+            }
+            let Some(token) = lex(i) else {
+                continue;
+            };
+            let len = token.0.range.end.position - location.position;
+            match longest {
+                Some((prev, index, _)) if prev > len || (prev == len && index < i) => {}
+                _ => longest = Some((len, i, token)),
+            }
+        }
+        longest.map(|(_, _, token)| token)
+    }
+
+    /// Lexes `src` from the start, yielding each token that isn't skipped.
+    ///
+    /// If no token matches, an error is yielded and the iterator ends.
+    pub fn tokens<'lexer, 'src>(&'lexer self, src: &'src str) -> Tokens<'lexer, 'src> {
+        Tokens {
+            lexer: self,
+            src,
+            location: Location { position: 0 },
+            failed: false,
+        }
+    }
+}
+
+/// An iterator over the tokens in some input, created by [`Lexer::tokens`].
+#[derive(Debug, Clone)]
+pub struct Tokens<'lexer, 'src> {
+    lexer: &'lexer Lexer,
+    src: &'src str,
+    location: Location,
+    failed: bool,
+}
+
+impl<'src> Tokens<'_, 'src> {
+    /// The location of the next token.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    fn error(&self) -> ParseError<'src> {
+        let mut error = ParseError {
+            location: self.location,
+            src: Some(self.src),
+            ..ParseError::default()
+        };
+        for &(token_type, skip) in &self.lexer.tokens {
+            if !skip {
+                error.add_expected(self.location, token_type);
+            }
+        }
+
+        let rest = &self.src[self.location.position..];
+        error.actual = rest
+            .get(..rest.chars().next().map_or(0, char::len_utf8))
+            .unwrap_or("");
+        error
+    }
+}
+
+impl<'src> Iterator for Tokens<'_, 'src> {
+    type Item = Result<AnyToken, ParseError<'src>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed && self.location.position < self.src.len() {
+            let Some((token, skip)) = self.lexer.lex_at(self.src, self.location) else {
+                self.failed = true;
+                return Some(Err(self.error()));
+            };
+
+            self.location = token.range.end;
+            if !skip {
+                return Some(Ok(token));
+            }
+        }
+
+        None
+    }
+}
+
+/// A set of tokens that can be lexed within some region of the input, like the inside of a
+/// string, switched to by [`PushMode`](crate::ast::PushMode).
+///
+/// ```
+/// # use rs_typed_parser::{lexer::LexerMode, token::TokenType};
+/// rs_typed_parser::define_token!(
+///     #[pattern(exact = "\"")]
+///     pub struct Quote;
+///     #[pattern(regex = r#"[^"]+"#)]
+///     pub struct StrText;
+/// );
+///
+/// pub struct StrMode;
+///
+/// impl LexerMode for StrMode {
+///     fn allows(token_type: &'static TokenType) -> bool {
+///         [TokenType::of::<Quote>(), TokenType::of::<StrText>()].contains(&token_type)
+///     }
+/// }
+/// ```
+pub trait LexerMode: 'static {
+    /// Whether `token_type` can be lexed in this mode.
+    fn allows(token_type: &'static TokenType) -> bool;
+}
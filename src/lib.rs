@@ -3,7 +3,11 @@ extern crate alloc;
 extern crate either;
 extern crate once_cell;
 extern crate regex;
+#[cfg(feature = "std")]
+extern crate std;
 
+#[doc(hidden)]
+pub use alloc::string::String;
 #[doc(hidden)]
 pub use either::Either;
 #[doc(hidden)]
@@ -12,15 +16,24 @@ pub use once_cell::sync::Lazy;
 pub use regex::Regex;
 
 pub mod ast;
+pub mod cst;
+pub mod diagnostic;
+pub mod lexer;
+pub(crate) mod memo;
 pub mod parse;
 pub mod token;
+pub mod trace;
 pub(crate) mod utils;
 pub(crate) mod internal_prelude {
-    pub use alloc::{boxed::Box, vec::Vec};
+    pub use alloc::{
+        boxed::Box,
+        string::{String, ToString},
+        vec::Vec,
+    };
 }
 
-pub use ast::{parse_tree, Rule};
-pub use parse::ParseError;
+pub use ast::{parse_cst, parse_tree, parse_tree_dyn, parse_tree_recover, Rule};
+pub use parse::{LineColumn, LineIndex, ParseError, ParseOptions};
 pub use token::TokenDef;
 
 #[doc(hidden)]
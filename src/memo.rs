@@ -0,0 +1,77 @@
+use core::any::TypeId;
+
+use crate::parse::{Location, ParseError, TokenBuf, TokenBufData};
+
+#[cfg(not(feature = "std"))]
+type Map<K, V> = alloc::collections::BTreeMap<K, V>;
+#[cfg(feature = "std")]
+type Map<K, V> = std::collections::HashMap<K, V>;
+
+/// Everything that can affect the outcome of parsing a rule at a given location.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct MemoKey<A: TokenBufData> {
+    node_id: TypeId,
+    location: Location,
+    /// The source being parsed, which is a prefix of the original source within a
+    /// [`CompoundToken`](crate::ast::CompoundToken).
+    src: *const str,
+    prefer_continue: bool,
+    /// The id of the current lexer mode and the modes below it that it can pop back to.
+    mode_id: u64,
+    look_ahead: TokenBuf<A>,
+    /// The id of the chain of rules after this one.
+    chain_id: u64,
+}
+
+impl<A: TokenBufData> MemoKey<A> {
+    pub fn new(
+        node_id: TypeId,
+        location: Location,
+        src: &str,
+        prefer_continue: bool,
+        mode_id: u64,
+        look_ahead: &TokenBuf<A>,
+        chain_id: u64,
+    ) -> Self {
+        Self {
+            node_id,
+            location,
+            src,
+            prefer_continue,
+            mode_id,
+            look_ahead: look_ahead.clone(),
+            chain_id,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseEntry {
+    pub result: Result<Location, Location>,
+    pub error: ParseError<'static>,
+    /// Whether the parse passed a [`Cut`](crate::ast::Cut).
+    pub cut: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct PreParseEntry<A: TokenBufData> {
+    pub result: Result<(), Location>,
+    pub look_ahead: TokenBuf<A>,
+}
+
+/// Results of previous attempts to parse rules, so repeated attempts from the same state don't
+/// have to redo the work.
+#[derive(Debug)]
+pub(crate) struct MemoTable<A: TokenBufData> {
+    pub parses: Map<MemoKey<A>, ParseEntry>,
+    pub pre_parses: Map<MemoKey<A>, PreParseEntry<A>>,
+}
+
+impl<A: TokenBufData> Default for MemoTable<A> {
+    fn default() -> Self {
+        Self {
+            parses: Map::new(),
+            pre_parses: Map::new(),
+        }
+    }
+}
@@ -1,19 +1,25 @@
 use core::{
+    any::{Any, TypeId},
     cmp::Ordering,
-    fmt::Debug,
+    fmt::{self, Debug},
     hash::Hash,
     marker::PhantomData,
     ops::{Add, AddAssign, Deref, DerefMut, Index, IndexMut, Range, Sub, SubAssign},
+    ptr,
     slice::SliceIndex,
 };
 
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
 use regex::Regex;
 
 use crate::{
-    ast::{PreParseState, RuleParseResult, RuleType},
+    ast::{PreParseState, RuleParseFailed, RuleParseResult, RuleType},
     internal_prelude::*,
+    lexer::{Lexer, LexerMode, PatternMatches},
+    memo::{MemoKey, MemoTable, ParseEntry, PreParseEntry},
     token::{AnyToken, TokenType},
-    utils::default,
+    trace::{RuleName, TraceEvent, Tracer},
+    utils::{combine_id, default},
     Rule,
 };
 
@@ -53,6 +59,12 @@ impl LocationRange {
             end: self.end.max(other.end),
         }
     }
+
+    /// Returns the slice of `src` covered by this range, or an empty string if it's out of bounds.
+    pub fn slice(self, src: &str) -> &str {
+        src.get(self.start.position..self.end.position)
+            .unwrap_or_default()
+    }
 }
 
 impl Add<usize> for Location {
@@ -117,6 +129,99 @@ impl SubAssign<usize> for LocationRange {
     }
 }
 
+/// A 1-based line and column within a source string.
+///
+/// `column` counts UTF-8 bytes from the start of the line, while `column_utf16` counts UTF-16
+/// code units, as expected by editors and the language server protocol.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+    pub column_utf16: usize,
+}
+
+impl fmt::Display for LineColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Maps byte [`Location`]s in a source string to [`LineColumn`]s.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'src> {
+    src: &'src str,
+    line_starts: Vec<usize>,
+}
+
+impl<'src> LineIndex<'src> {
+    pub fn new(src: &'src str) -> Self {
+        let line_starts = core::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { src, line_starts }
+    }
+
+    pub fn src(&self) -> &'src str {
+        self.src
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the 0-based index of the line containing the byte at `position`.
+    fn line_index(&self, position: usize) -> usize {
+        match self.line_starts.binary_search(&position) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        }
+    }
+
+    /// Returns the byte range of the 1-based `line`, excluding the line terminator.
+    pub fn line_range(&self, line: usize) -> Option<LocationRange> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.src.len(), |&next| next - 1);
+        let end = match self.src[..end].ends_with('\r') {
+            true => end - 1,
+            false => end,
+        };
+
+        Some(LocationRange {
+            start: Location { position: start },
+            end: Location { position: end },
+        })
+    }
+
+    /// Returns the text of the 1-based `line`, excluding the line terminator.
+    pub fn line(&self, line: usize) -> Option<&'src str> {
+        let range = self.line_range(line)?;
+        Some(&self.src[range.start.position..range.end.position])
+    }
+
+    pub fn line_column(&self, location: Location) -> LineColumn {
+        let mut position = location.position.min(self.src.len());
+        while !self.src.is_char_boundary(position) {
+            position -= 1;
+        }
+
+        let line = self.line_index(position);
+        let prefix = &self.src[self.line_starts[line]..position];
+
+        LineColumn {
+            line: line + 1,
+            column: prefix.len() + 1,
+            column_utf16: prefix.encode_utf16().count() + 1,
+        }
+    }
+
+    pub fn line_column_range(&self, range: LocationRange) -> Range<LineColumn> {
+        self.line_column(range.start)..self.line_column(range.end)
+    }
+}
+
 pub fn lex_regex(
     regex: &Regex,
     capture: usize,
@@ -145,6 +250,49 @@ pub fn lex_exact(pattern: &str, src: &str, location: Location) -> Option<Locatio
         })
 }
 
+/// Like [`lex_exact`], but ignoring ASCII case, so `select` also matches `SELECT` and `Select`.
+pub fn lex_exact_ignore_case(
+    pattern: &str,
+    src: &str,
+    location: Location,
+) -> Option<LocationRange> {
+    src[location.position..]
+        .get(..pattern.len())?
+        .eq_ignore_ascii_case(pattern)
+        .then_some(LocationRange {
+            start: location,
+            end: location + pattern.len(),
+        })
+}
+
+/// Like [`lex_exact`], but fails if the match is followed by a character that could continue an
+/// identifier, so the keyword `if` doesn't match the start of `iffy`.
+///
+/// Those characters are given by `ident_char`, a regex anchored at the start of the input, or
+/// are letters, digits and `_` if it's `None`.
+pub fn lex_keyword(
+    pattern: &str,
+    ignore_case: bool,
+    ident_char: Option<&Regex>,
+    src: &str,
+    location: Location,
+) -> Option<LocationRange> {
+    let range = match ignore_case {
+        true => lex_exact_ignore_case(pattern, src, location)?,
+        false => lex_exact(pattern, src, location)?,
+    };
+    let rest = &src[range.end.position..];
+    let continues = match ident_char {
+        Some(regex) => regex.is_match(rest),
+        None => rest
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_'),
+    };
+
+    (!continues).then_some(range)
+}
+
 mod private {
     use super::*;
 
@@ -155,13 +303,17 @@ mod private {
 }
 
 #[derive(Debug)]
-pub(crate) struct CxTypeImpl<const LA: usize> {}
+pub(crate) struct CxTypeImpl<A: TokenBufData> {
+    _look_ahead: PhantomData<A>,
+}
 
-impl<const LA: usize> private::ContextType for CxTypeImpl<LA> {
-    type LookAhead = [Option<AnyToken>; LA];
+impl<A: TokenBufData> private::ContextType for CxTypeImpl<A> {
+    type LookAhead = A;
 
     fn child(&self) -> Self {
-        Self {}
+        Self {
+            _look_ahead: PhantomData,
+        }
     }
 }
 
@@ -173,20 +325,186 @@ impl<Cx: private::ContextType> CxType for Cx {}
 pub struct ParseContext<'src, 'cx, Cx: CxType> {
     src: &'src str,
     error: &'cx mut ParseError<'static>,
+    diagnostics: &'cx mut Vec<ParseError<'static>>,
     location: &'cx mut Location,
     look_ahead: &'cx mut TokenBuf<Cx::LookAhead>,
+    memo: Option<&'cx mut MemoTable<Cx::LookAhead>>,
+    state: &'cx mut GlobalState,
+    tracer: Option<&'cx dyn Tracer>,
+    lexer: Option<&'cx Lexer>,
+    /// How many rules are being parsed that don't skip trivia.
+    trivia_suspended: usize,
+    mode: Option<&'cx ModeFrame<'cx>>,
+    /// Identifies the rules that follow the innermost rule being parsed, which end any modes it
+    /// switches to.
+    rule_end: *const (),
     discard: bool,
     prefer_continue: bool,
     cx_type: Cx,
     _cx_type: PhantomData<&'cx Cx>,
 }
 
+/// A [`LexerMode`], or the lack of one.
+#[derive(Debug, Clone, Copy)]
+struct ModeType {
+    id: TypeId,
+    allows: fn(&'static TokenType) -> bool,
+}
+
+impl ModeType {
+    fn of<M: LexerMode>() -> Self {
+        Self {
+            id: TypeId::of::<M>(),
+            allows: M::allows,
+        }
+    }
+}
+
+/// A switch to a mode, which lasts until the rules in `until` are reached.
+#[derive(Debug)]
+struct ModeFrame<'a> {
+    /// The mode switched to, or `None` for the default of allowing every token.
+    mode: Option<ModeType>,
+    /// The frame of the mode to switch to when this one is popped.
+    below: Option<&'a ModeFrame<'a>>,
+    /// The frame that was current before this one.
+    restore: Option<&'a ModeFrame<'a>>,
+    until: *const (),
+    /// An id for this mode and the ones below it, which is the same for the same stack of modes.
+    id: u64,
+}
+
+impl<'a> ModeFrame<'a> {
+    fn new(
+        mode: Option<ModeType>,
+        below: Option<&'a ModeFrame<'a>>,
+        restore: Option<&'a ModeFrame<'a>>,
+        until: *const (),
+    ) -> Self {
+        let below_id = below.map_or(0, |below| below.id);
+        Self {
+            mode,
+            below,
+            restore,
+            until,
+            id: combine_id(below_id, mode.map(|mode| mode.id)),
+        }
+    }
+}
+
+/// A rule that's currently being parsed.
+#[derive(Debug)]
+struct ActiveRule {
+    node_id: TypeId,
+    name: &'static str,
+    location: Location,
+    /// Identifies the rule that follows this one, so we can tell whether a later rule is nested
+    /// inside this one or merely comes after it.
+    next: *const (),
+    /// The length of the chain of rules starting from `next`.
+    next_len: usize,
+    /// The index of the outermost active rule that starts at the same location as this one.
+    same_location: usize,
+    /// Whether this rule is a repetition, which loops back to itself after each item.
+    repeats: bool,
+}
+
+/// State that's shared by every context in a parse, no matter how it's been updated.
+#[derive(Debug, Default)]
+pub(crate) struct GlobalState {
+    fatal: Option<ParseError<'static>>,
+    /// How many [`isolated_parse`](ParseContext::isolated_parse)s are in progress, whose failures
+    /// only decide what gets parsed.
+    speculative: usize,
+    active: Vec<ActiveRule>,
+    /// How many times each rule is active, which is how deeply it's nested within itself.
+    depths: BTreeMap<TypeId, usize>,
+    max_depth: usize,
+    fuel: Option<u64>,
+    cancel: Option<CancelFn>,
+    /// How far the patterns of the [`Lexer`]'s tokens match at each location, for a given length
+    /// of the source.
+    pattern_matches: BTreeMap<(Location, usize), PatternMatches>,
+    /// The token of each type lexed at each location, for a given length of the source, so
+    /// trying it again, e.g. for another alternative, doesn't lex it again.
+    lexed: BTreeMap<(Location, usize, TypeId), Option<AnyToken>>,
+    trivia: Option<TriviaFn>,
+    /// Where the trivia at each location ends, for a given length of the source and lexer mode.
+    skipped: BTreeMap<(Location, usize, Option<TypeId>), Location>,
+    /// The tokens that have been parsed into the tree so far, if they're being recorded.
+    recorded: Option<Vec<AnyToken>>,
+    /// The range of the tokens parsed since the innermost [`spanning`](ParseContext::spanning)
+    /// began.
+    span: Option<LocationRange>,
+    /// Values worked out while looking ahead, by the rule that found them and the range they were
+    /// found over, for when it's parsed for real.
+    checked: BTreeMap<(TypeId, LocationRange), Box<dyn Any>>,
+    /// Input rejected by [`reject`](ParseContext::reject) while parsing speculatively, which is
+    /// reported once the parse that's committed to reaches it.
+    rejected: Vec<(LocationRange, String)>,
+    /// Whether the innermost speculative parse has passed a [`Cut`](crate::ast::Cut).
+    cut: bool,
+}
+
+impl GlobalState {
+    /// Takes the rejection recorded over input that includes `location`, if any, as the error to
+    /// report for it.
+    fn take_rejected(&mut self, location: Location) -> Option<ParseError<'static>> {
+        let index = self
+            .rejected
+            .iter()
+            .position(|(range, _)| range.start <= location && location < range.end)?;
+        let (range, message) = self.rejected.remove(index);
+        Some(ParseError {
+            location: range.start,
+            kind: ParseErrorKind::Invalid { range, message },
+            ..default()
+        })
+    }
+}
+
+/// The state of the tokens parsed at some point, created by [`ParseContext::token_mark`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TokenMark {
+    recorded: usize,
+    span: Option<LocationRange>,
+}
+
+/// The context trivia is parsed in, which doesn't depend on the rest of the grammar.
+type TriviaCx = CxTypeImpl<[Option<AnyToken>; 1]>;
+
+/// Returns where the trivia at the location of a context ends.
+type TriviaFn = fn(ParseContext<TriviaCx>) -> Location;
+
+fn skip_trivia<T: Rule>(mut cx: ParseContext<TriviaCx>) -> Location {
+    loop {
+        let location = cx.location();
+        *cx.look_ahead_mut() = default();
+
+        match T::parse(cx.by_ref(), default()) {
+            Ok(_) if cx.location() > location => {}
+            _ => return location,
+        }
+    }
+}
+
+/// A callback that's polled to tell whether a parse should stop early.
+#[derive(Clone)]
+struct CancelFn(Arc<dyn Fn() -> bool + Send + Sync>);
+
+impl Debug for CancelFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CancelFn(..)")
+    }
+}
+
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct ParseContextUpdate<'src, 'cx, Cx: CxType> {
     pub src: Option<&'src str>,
     pub location: Option<&'cx mut Location>,
     pub error: Option<&'cx mut ParseError<'static>>,
+    pub diagnostics: Option<&'cx mut Vec<ParseError<'static>>>,
     pub look_ahead: Option<&'cx mut TokenBuf<Cx::LookAhead>>,
     pub prefer_continue: Option<bool>,
     pub discard: Option<bool>,
@@ -198,6 +516,7 @@ impl<Cx: CxType> Default for ParseContextUpdate<'_, '_, Cx> {
             src: None,
             location: None,
             error: None,
+            diagnostics: None,
             look_ahead: None,
             prefer_continue: None,
             discard: None,
@@ -205,29 +524,194 @@ impl<Cx: CxType> Default for ParseContextUpdate<'_, '_, Cx> {
     }
 }
 
-pub(crate) type SizedParseContext<'src, 'cx, const LA: usize> =
-    ParseContext<'src, 'cx, CxTypeImpl<LA>>;
+/// Settings for a parse.
+///
+/// ```
+/// # use rs_typed_parser::parse::ParseOptions;
+/// let options = ParseOptions::new().memoize(true);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParseOptions<'t> {
+    memoize: bool,
+    look_ahead: usize,
+    max_depth: usize,
+    fuel: Option<u64>,
+    cancel: Option<CancelFn>,
+    tracer: Option<&'t dyn Tracer>,
+    lexer: Option<&'t Lexer>,
+    trivia: Option<TriviaFn>,
+    lossless: bool,
+}
+
+impl Default for ParseOptions<'_> {
+    fn default() -> Self {
+        Self {
+            memoize: false,
+            look_ahead: 1,
+            max_depth: 128,
+            fuel: None,
+            cancel: None,
+            tracer: None,
+            lexer: None,
+            trivia: None,
+            lossless: false,
+        }
+    }
+}
+
+impl<'t> ParseOptions<'t> {
+    pub fn new() -> Self {
+        default()
+    }
+
+    /// Reports what happens during the parse to `tracer`, e.g. a [`TraceLog`].
+    ///
+    /// [`TraceLog`]: crate::trace::TraceLog
+    pub fn tracer(mut self, tracer: &'t dyn Tracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Matches the patterns of the tokens that `lexer` contains before lexing them, so that the
+    /// ones that can't match at a location are ruled out together rather than each being tried
+    /// in turn, e.g. for each alternative of a large [`Either`].
+    ///
+    /// [`Either`]: crate::Either
+    pub fn lexer(mut self, lexer: &'t Lexer) -> Self {
+        self.lexer = Some(lexer);
+        self
+    }
+
+    /// Skips `T`, e.g. `Ignore<Either<Space, Comment>>`, before every token, as many times as it
+    /// matches, so rules don't each have to ignore it themselves.
+    ///
+    /// Skipping can be turned off within a rule with [`NoTrivia`].
+    ///
+    /// [`NoTrivia`]: crate::ast::NoTrivia
+    pub fn trivia<T: Rule>(mut self) -> Self {
+        self.trivia = Some(skip_trivia::<T>);
+        self
+    }
 
-impl<'src, const LA: usize> SizedParseContext<'src, 'static, LA> {
+    /// Records the tokens of the tree as it's parsed, to build a [`Cst`](crate::cst::Cst).
+    pub(crate) fn lossless(mut self, lossless: bool) -> Self {
+        self.lossless = lossless;
+        self
+    }
+
+    /// Sets how many tokens can be looked ahead when the lookahead is chosen at runtime, as by
+    /// [`parse_tree_dyn`](Self::parse_tree_dyn). Defaults to 1.
+    ///
+    /// Entry points that take the lookahead as a const generic ignore this.
+    pub fn look_ahead(mut self, look_ahead: usize) -> Self {
+        self.look_ahead = look_ahead;
+        self
+    }
+
+    /// Caches the outcome of speculative parses, like those done by [`Backtrack`] and
+    /// [`Either`](crate::Either), so they run at most once per rule and location.
+    ///
+    /// This trades memory for avoiding exponential time on heavily nested grammars.
+    ///
+    /// [`Backtrack`]: crate::ast::Backtrack
+    pub fn memoize(mut self, memoize: bool) -> Self {
+        self.memoize = memoize;
+        self
+    }
+
+    /// Sets how many times a rule can be nested within itself before the parse fails with
+    /// [`ParseErrorKind::NestingTooDeep`]. Defaults to 128.
+    ///
+    /// Parsing recurses for each level of nesting, so this guards against running out of stack
+    /// on input like `((((...))))`, of which the default allows 128 levels. Raising it may require
+    /// running the parse on a thread with a larger stack.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limits the parse to `fuel` steps, after which it fails with
+    /// [`ParseErrorKind::OutOfFuel`]. Unlimited by default.
+    ///
+    /// A step is taken each time a rule is attempted or a token is lexed, so this bounds the work
+    /// done on input that makes the grammar backtrack heavily.
+    pub fn fuel(mut self, fuel: impl Into<Option<u64>>) -> Self {
+        self.fuel = fuel.into();
+        self
+    }
+
+    /// Polls `cancel` on every step of the parse, failing with [`ParseErrorKind::Cancelled`] once
+    /// it returns `true`.
+    ///
+    /// This is called often, so it should be cheap, e.g. checking an `AtomicBool` or a deadline.
+    pub fn cancel_when(mut self, cancel: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        self.cancel = Some(CancelFn(Arc::new(cancel)));
+        self
+    }
+}
+
+pub(crate) type RootParseContext<'src, 'cx, A> = ParseContext<'src, 'cx, CxTypeImpl<A>>;
+
+impl<'src, A: TokenBufData> RootParseContext<'src, 'static, A> {
+    /// Runs `f` with a new context, returning its result along with the error that caused it to
+    /// fail, if any, and any errors that were recovered from.
     pub fn new_with<R>(
         src: &'src str,
-        f: impl FnOnce(SizedParseContext<'src, '_, LA>) -> R,
-    ) -> (R, ParseError<'src>) {
-        let cx_type = CxTypeImpl::<LA> {};
+        options: &ParseOptions<'_>,
+        f: impl FnOnce(RootParseContext<'src, '_, A>) -> RuleParseResult<R>,
+    ) -> (RuleParseResult<R>, ParseError<'src>, Vec<ParseError<'src>>) {
+        let cx_type = CxTypeImpl {
+            _look_ahead: PhantomData,
+        };
         let mut error = default();
+        let mut diagnostics = Vec::new();
+        let mut memo = options.memoize.then(MemoTable::default);
+        let mut state = GlobalState {
+            max_depth: options.max_depth,
+            fuel: options.fuel,
+            cancel: options.cancel.clone(),
+            trivia: options.trivia,
+            recorded: options.lossless.then(Vec::new),
+            ..default()
+        };
 
         let ret = f(ParseContext {
             src,
             error: &mut error,
+            diagnostics: &mut diagnostics,
             location: &mut Location { position: 0 },
             discard: false,
-            look_ahead: &mut default(),
+            look_ahead: &mut TokenBuf::new(options.look_ahead),
+            memo: memo.as_mut(),
+            state: &mut state,
+            tracer: options.tracer,
+            lexer: options.lexer,
+            trivia_suspended: 0,
+            mode: None,
+            rule_end: ptr::null(),
             prefer_continue: true,
             cx_type,
             _cx_type: PhantomData,
         });
 
-        (ret, error)
+        if ret.is_err() {
+            // the parse failed within input that a speculative parse rejected, which is the more
+            // useful error to report
+            if let Some(rejected) = state.take_rejected(error.location) {
+                error = rejected;
+            }
+        }
+
+        match state.fatal {
+            Some(fatal) => (
+                Err(RuleParseFailed {
+                    location: fatal.location,
+                }),
+                fatal,
+                diagnostics,
+            ),
+            None => (ret, error, diagnostics),
+        }
     }
 }
 
@@ -236,9 +720,17 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
         let ParseContext {
             src,
             error,
+            diagnostics,
             location,
             discard,
             look_ahead,
+            memo,
+            state,
+            tracer,
+            lexer,
+            trivia_suspended,
+            mode,
+            rule_end,
             prefer_continue,
             cx_type,
             ..
@@ -246,9 +738,17 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
         ParseContext {
             src,
             error,
+            diagnostics,
             location,
             discard: *discard,
             look_ahead,
+            memo: memo.as_deref_mut(),
+            state,
+            tracer: *tracer,
+            lexer: *lexer,
+            trivia_suspended: *trivia_suspended,
+            mode: *mode,
+            rule_end: *rule_end,
             prefer_continue: *prefer_continue,
             cx_type: cx_type.child(),
             _cx_type: PhantomData,
@@ -275,6 +775,7 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
             src,
             location,
             error,
+            diagnostics,
             look_ahead,
             discard,
             prefer_continue,
@@ -283,6 +784,7 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
         ParseContext {
             src: src.unwrap_or(self.src),
             error: error.unwrap_or(self.error),
+            diagnostics: diagnostics.unwrap_or(self.diagnostics),
             location: location.unwrap_or(self.location),
             look_ahead: look_ahead.unwrap_or(self.look_ahead),
             discard: discard.unwrap_or(self.discard),
@@ -298,18 +800,530 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
         })
     }
 
+    /// Marks the innermost rule being parsed as a repetition, so that an item that matches
+    /// nothing is reported as such rather than as left recursion.
+    pub(crate) fn repeating(self) -> Self {
+        if let Some(rule) = self.state.active.last_mut() {
+            rule.repeats = true;
+        }
+        self
+    }
+
+    /// Stops skipping trivia before tokens, until a matching call to
+    /// [`resume_trivia`](Self::resume_trivia).
+    pub fn suspend_trivia(mut self) -> Self {
+        self.trivia_suspended += 1;
+        self
+    }
+
+    pub fn resume_trivia(mut self) -> Self {
+        self.trivia_suspended = self.trivia_suspended.saturating_sub(1);
+        self
+    }
+
+    /// Runs `f` with only the tokens allowed by the mode `M` being lexed, until the rule being
+    /// parsed ends, e.g. from [`TransformRule::update_context`](crate::ast::TransformRule).
+    ///
+    /// Tokens that were looked ahead before switching modes are checked against the new mode
+    /// before being used, and lexed again if the new mode skips different trivia before them.
+    pub fn push_mode<M: LexerMode, R>(self, f: impl FnOnce(ParseContext<'src, '_, Cx>) -> R) -> R {
+        let frame = ModeFrame::new(
+            Some(ModeType::of::<M>()),
+            self.mode,
+            self.mode,
+            self.rule_end,
+        );
+        self.with_mode_frame(&frame, f)
+    }
+
+    /// Like [`push_mode`](Self::push_mode), but switching back to the mode that was active
+    /// before the current one, e.g. for an expression within a string.
+    pub fn pop_mode<R>(self, f: impl FnOnce(ParseContext<'src, '_, Cx>) -> R) -> R {
+        let below = self.mode.and_then(|frame| frame.below);
+        let frame = ModeFrame::new(
+            below.and_then(|frame| frame.mode),
+            below.and_then(|frame| frame.below),
+            self.mode,
+            self.rule_end,
+        );
+        self.with_mode_frame(&frame, f)
+    }
+
+    fn with_mode_frame<'a, R>(
+        self,
+        frame: &'a ModeFrame<'a>,
+        f: impl FnOnce(ParseContext<'src, 'a, Cx>) -> R,
+    ) -> R
+    where
+        'cx: 'a,
+    {
+        let mut cx: ParseContext<'src, 'a, Cx> = self;
+        cx.mode = Some(frame);
+        f(cx)
+    }
+
+    /// Switches back from any modes that end before `rule`, because the rule that switched to
+    /// them is followed by it.
+    pub(crate) fn end_modes(mut self, rule: &RuleType<Cx>) -> Self {
+        let rule = rule as *const RuleType<Cx> as *const ();
+        while let Some(frame) = self.mode.filter(|frame| ptr::eq(frame.until, rule)) {
+            self.mode = frame.restore;
+        }
+        self
+    }
+
+    /// The id of the current mode and the ones below it, or 0 if no mode was switched to.
+    fn mode_id(&self) -> u64 {
+        self.mode.map_or(0, |frame| frame.id)
+    }
+
+    /// Whether `token_type` can be lexed in the current mode.
+    pub fn mode_allows(&self, token_type: &'static TokenType) -> bool {
+        match self.mode.and_then(|frame| frame.mode) {
+            Some(mode) => (mode.allows)(token_type),
+            None => true,
+        }
+    }
+
+    /// Returns where the next token starts if it's lexed at `location`, after any trivia set by
+    /// [`ParseOptions::trivia`].
+    ///
+    /// The trivia is parsed as part of this parse, e.g. using up its fuel, but can't fail it or
+    /// add to the tree.
+    pub fn skip_trivia(&mut self, location: Location) -> Location {
+        let Some(trivia) = self.state.trivia.filter(|_| self.trivia_suspended == 0) else {
+            return location;
+        };
+        let key = (
+            location,
+            self.src.len(),
+            self.mode.and_then(|frame| frame.mode).map(|mode| mode.id),
+        );
+        if let Some(&end) = self.state.skipped.get(&key) {
+            return end;
+        }
+
+        let mark = self.token_mark();
+        self.state.speculative += 1;
+        let end = trivia(ParseContext {
+            src: self.src,
+            error: &mut default(),
+            diagnostics: &mut Vec::new(),
+            location: &mut location.clone(),
+            discard: true,
+            look_ahead: &mut default(),
+            memo: None,
+            state: self.state,
+            tracer: self.tracer,
+            lexer: self.lexer,
+            // trivia isn't skipped within itself
+            trivia_suspended: 1,
+            mode: self.mode,
+            rule_end: self.rule_end,
+            prefer_continue: self.prefer_continue,
+            cx_type: CxTypeImpl {
+                _look_ahead: PhantomData,
+            },
+            _cx_type: PhantomData,
+        });
+        self.state.speculative -= 1;
+        self.rollback_tokens(mark);
+
+        self.state.skipped.insert(key, end);
+        end
+    }
+
     pub fn src(&self) -> &'src str {
         self.src
     }
 
     pub fn error_mut(&mut self) -> &mut ParseError<'static> {
-        &mut self.error
+        self.error
+    }
+
+    /// Records an error that was recovered from, so that parsing can continue.
+    pub fn push_diagnostic(&mut self, error: ParseError<'static>) {
+        self.diagnostics.push(error);
     }
 
     pub fn location(&self) -> Location {
         *self.location
     }
 
+    /// Stops the parse with `error`, which can't be recovered from or backtracked past, e.g. when
+    /// it runs out of fuel.
+    ///
+    /// Only the first call takes effect.
+    pub fn abort(&mut self, error: ParseError<'static>) {
+        self.state.fatal.get_or_insert(error);
+    }
+
+    /// Rejects the input at `range` with `message`, because it matched but doesn't make a valid
+    /// value, e.g. a number that's too large for its type.
+    ///
+    /// This fails the parse with [`ParseErrorKind::Invalid`], which [`Recover`] can skip past like
+    /// any other error. A rejection isn't a mismatch, so it isn't avoided by parsing something
+    /// else instead: when the input is only being parsed speculatively, e.g. by
+    /// [`Backtrack`](crate::ast::Backtrack) to see if it matches, the rejection is kept until the
+    /// parse that's committed to reaches the same input.
+    ///
+    /// [`Recover`]: crate::ast::Recover
+    pub fn reject(&mut self, range: LocationRange, message: String) -> RuleParseFailed {
+        if self.state.speculative > 0 {
+            if !self.state.rejected.iter().any(|(other, _)| *other == range) {
+                self.state.rejected.push((range, message));
+            }
+        } else {
+            *self.error = ParseError {
+                location: range.start,
+                kind: ParseErrorKind::Invalid { range, message },
+                ..default()
+            };
+        }
+        RuleParseFailed {
+            location: range.start,
+        }
+    }
+
+    /// Fails with the rejection recorded while parsing speculatively over input that includes
+    /// `location`, once it's parsed for real.
+    pub(crate) fn reach(&mut self, location: Location) -> RuleParseResult<()> {
+        if self.state.speculative > 0 || self.state.rejected.is_empty() {
+            return Ok(());
+        }
+        match self.state.take_rejected(location) {
+            Some(rejected) => {
+                *self.error = rejected;
+                Err(RuleParseFailed { location })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the input is only being parsed to decide what to parse, e.g. by
+    /// [`Backtrack`](crate::ast::Backtrack).
+    pub(crate) fn is_speculative(&self) -> bool {
+        self.state.speculative > 0
+    }
+
+    /// Whether the parse was stopped by [`abort`](Self::abort), meaning any failure should be
+    /// propagated as-is rather than trying alternatives.
+    pub fn is_aborted(&self) -> bool {
+        self.state.fatal.is_some()
+    }
+
+    /// Reports the event made by `event` to the tracer, if there is one.
+    fn trace(&self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(tracer) = self.tracer {
+            tracer.event(event());
+        }
+    }
+
+    /// Lexes a token of `token_type` at `location`, taking a step of the parse.
+    pub(crate) fn lex(
+        &mut self,
+        token_type: &'static TokenType,
+        location: Location,
+    ) -> RuleParseResult<Option<AnyToken>> {
+        self.step(location)?;
+        let src = self.src;
+        let key = (location, src.len(), token_type.token_id());
+        let token = match self.state.lexed.get(&key) {
+            Some(&token) => token,
+            None => {
+                let token = self.lex_uncached(token_type, location);
+                self.state.lexed.insert(key, token);
+                token
+            }
+        };
+        self.trace(|| TraceEvent::Lex {
+            token_type,
+            location,
+            result: token.map(|token| token.range),
+        });
+        Ok(token)
+    }
+
+    fn lex_uncached(
+        &mut self,
+        token_type: &'static TokenType,
+        location: Location,
+    ) -> Option<AnyToken> {
+        let src = self.src;
+        let Some((lexer, index)) = self
+            .lexer
+            .and_then(|lexer| Some((lexer, lexer.index_of(token_type)?)))
+        else {
+            return token_type.try_lex(src, location);
+        };
+
+        let matches = self
+            .state
+            .pattern_matches
+            .entry((location, src.len()))
+            .or_insert_with(|| lexer.pattern_matches(src, location));
+        lexer.max_len(matches, index)?;
+        token_type.try_lex(src, location)
+    }
+
+    /// Records `token` as part of the tree, unless it's being discarded, and adds it to the span
+    /// of the enclosing rules.
+    pub(crate) fn record_token(&mut self, token: AnyToken) {
+        self.extend_span(token.range);
+        if let (Some(recorded), false) = (&mut self.state.recorded, self.discard) {
+            recorded.push(token);
+        }
+    }
+
+    pub(crate) fn extend_span(&mut self, range: LocationRange) {
+        self.state.span = Some(self.state.span.map_or(range, |span| span.combine(range)));
+    }
+
+    /// Runs `f`, returning the range covered by the tokens it parses, if any, and adding it to the
+    /// span of the enclosing rules if `include`.
+    pub(crate) fn spanning<R>(
+        &mut self,
+        include: bool,
+        f: impl FnOnce(ParseContext<'src, '_, Cx>) -> R,
+    ) -> (R, Option<LocationRange>) {
+        let outer = self.state.span.take();
+        let out = f(self.by_ref());
+        let span = core::mem::replace(&mut self.state.span, outer);
+        if let (Some(span), true) = (span, include) {
+            self.extend_span(span);
+        }
+        (out, span)
+    }
+
+    /// Returns the tokens recorded so far, to [`rollback_tokens`](Self::rollback_tokens) to if
+    /// the parse doesn't end up in the tree.
+    pub(crate) fn token_mark(&self) -> TokenMark {
+        TokenMark {
+            recorded: self.state.recorded.as_ref().map_or(0, Vec::len),
+            span: self.state.span,
+        }
+    }
+
+    pub(crate) fn rollback_tokens(&mut self, mark: TokenMark) {
+        if let Some(recorded) = &mut self.state.recorded {
+            recorded.truncate(mark.recorded);
+        }
+        self.state.span = mark.span;
+    }
+
+    /// Keeps `value`, which `K` worked out over `range` while looking ahead, so it doesn't have to
+    /// be worked out again once `range` is parsed for real.
+    pub(crate) fn keep_checked<K: 'static, T: 'static>(&mut self, range: LocationRange, value: T) {
+        self.state
+            .checked
+            .insert((TypeId::of::<K>(), range), Box::new(value));
+    }
+
+    /// Takes the value that `K` kept over `range` with [`keep_checked`](Self::keep_checked).
+    pub(crate) fn take_checked<K: 'static, T: 'static>(
+        &mut self,
+        range: LocationRange,
+    ) -> Option<T> {
+        let value = self.state.checked.remove(&(TypeId::of::<K>(), range))?;
+        value.downcast().ok().map(|value| *value)
+    }
+
+    pub(crate) fn take_recorded_tokens(&mut self) -> Vec<AnyToken> {
+        self.state.recorded.take().unwrap_or_default()
+    }
+
+    /// Stores `token` in the lookahead buffer at `dist`.
+    pub(crate) fn fill_look_ahead(&mut self, dist: usize, token: AnyToken) {
+        // the tokens after a different one were lexed from where it ended, so they're stale
+        if self.look_ahead[dist] != Some(token) {
+            self.look_ahead[dist + 1..].fill(None);
+        }
+        self.look_ahead[dist] = Some(token);
+        self.trace(|| TraceEvent::LookAhead { dist, token });
+    }
+
+    /// Takes a step of the parse at `location`, aborting if it's out of fuel or has been cancelled.
+    pub(crate) fn step(&mut self, location: Location) -> RuleParseResult<()> {
+        let kind = match &mut self.state.fuel {
+            Some(0) => ParseErrorKind::OutOfFuel,
+            _ if self
+                .state
+                .cancel
+                .as_ref()
+                .is_some_and(|cancel| (cancel.0)()) =>
+            {
+                ParseErrorKind::Cancelled
+            }
+            fuel => {
+                if let Some(fuel) = fuel {
+                    *fuel -= 1;
+                }
+                return Ok(());
+            }
+        };
+
+        self.abort(ParseError {
+            location,
+            kind,
+            ..default()
+        });
+        Err(RuleParseFailed { location })
+    }
+
+    /// Runs `f` to parse, or pre-parse, the rule `T` at `location`, or aborts if doing so would
+    /// recurse forever because the rule is already being parsed there, or nest too deeply.
+    pub(crate) fn enter_rule<T: Rule, R>(
+        mut self,
+        location: Location,
+        pre_parse: bool,
+        next: &RuleType<Cx>,
+        f: impl FnOnce(ParseContext<'src, '_, Cx>) -> RuleParseResult<R>,
+    ) -> RuleParseResult<R> {
+        let node_id = TypeId::of::<T>();
+        let name = T::name();
+        let rule = RuleName::of::<T>();
+        self.trace(|| TraceEvent::Enter {
+            rule,
+            location,
+            pre_parse,
+        });
+
+        let out = self
+            .by_ref()
+            .enter_rule_inner(node_id, name, location, next, f);
+
+        let end = if pre_parse { location } else { self.location() };
+        self.trace(|| TraceEvent::Exit {
+            rule,
+            pre_parse,
+            result: out.as_ref().map(|_| end).map_err(|err| err.location),
+        });
+        out
+    }
+
+    fn enter_rule_inner<R>(
+        mut self,
+        node_id: TypeId,
+        name: &'static str,
+        location: Location,
+        next: &RuleType<Cx>,
+        f: impl FnOnce(ParseContext<'src, '_, Cx>) -> RuleParseResult<R>,
+    ) -> RuleParseResult<R> {
+        self.step(location)?;
+
+        if let Some(kind) = self.find_cycle(node_id, name, location, next) {
+            self.abort(ParseError {
+                location,
+                kind,
+                ..default()
+            });
+            return Err(RuleParseFailed { location });
+        }
+
+        // the depth is how many times the rule is already active, so a rule that isn't nested
+        // within itself has a depth of 0
+        let depth = self.state.depths.get(&node_id).copied().unwrap_or_default();
+        if depth > self.state.max_depth {
+            let max_depth = self.state.max_depth;
+            self.abort(ParseError {
+                location,
+                kind: ParseErrorKind::NestingTooDeep { max_depth },
+                ..default()
+            });
+            return Err(RuleParseFailed { location });
+        }
+        self.state.depths.insert(node_id, depth + 1);
+
+        let active = &mut self.state.active;
+        let same_location = match active.last() {
+            Some(rule) if rule.location == location => rule.same_location,
+            _ => active.len(),
+        };
+        let next_len = next.chain_len();
+        let next = next as *const RuleType<Cx> as *const ();
+        active.push(ActiveRule {
+            node_id,
+            name,
+            location,
+            next,
+            next_len,
+            same_location,
+            repeats: false,
+        });
+        let mut cx = self.by_ref();
+        cx.rule_end = next;
+        let out = f(cx);
+        self.state.active.pop();
+        if let Some(depth) = self.state.depths.get_mut(&node_id) {
+            *depth -= 1;
+        }
+        out
+    }
+
+    fn find_cycle(
+        &self,
+        node_id: TypeId,
+        name: &'static str,
+        location: Location,
+        next: &RuleType<Cx>,
+    ) -> Option<ParseErrorKind> {
+        // An active rule encloses this one if its next rule is still pending, rather than it
+        // having finished already and handed off to its next rule.
+        let encloses = |rule: &ActiveRule| next.chain_contains(rule.next, rule.next_len);
+
+        // rules are nested in the order they start, so only the innermost ones can start here
+        let active = &self.state.active;
+        let start = match active.last() {
+            Some(rule) if rule.location == location => rule.same_location,
+            _ => return None,
+        };
+
+        let first = start
+            + active[start..]
+                .iter()
+                .position(|rule| rule.node_id == node_id && encloses(rule))?;
+
+        let cycle: Vec<_> = active[first..]
+            .iter()
+            .filter(|rule| encloses(rule))
+            .map(|rule| rule.name)
+            .chain([name])
+            .collect();
+
+        // a repetition that comes straight back to itself has an item that matched nothing
+        match active[first].repeats && cycle.len() == 2 {
+            true => Some(ParseErrorKind::EmptyRepetition),
+            false => Some(ParseErrorKind::LeftRecursion { cycle }),
+        }
+    }
+
+    /// Runs `f`, and if it fails at `start`, reports `label` as expected there instead of whatever
+    /// `f` expected.
+    ///
+    /// Any trivia set by [`ParseOptions::trivia`] is skipped when determining the start, since
+    /// that's where the first token of `f` is lexed.
+    pub fn expecting<R>(
+        mut self,
+        label: &'static str,
+        start: Location,
+        f: impl FnOnce(ParseContext<'src, '_, Cx>) -> RuleParseResult<R>,
+    ) -> RuleParseResult<R> {
+        let start = self.skip_trivia(start);
+
+        let len = match self.error.location == start {
+            true => self.error.expected.len(),
+            false => 0,
+        };
+
+        let out = f(self.by_ref());
+
+        if out.is_err() {
+            self.error.replace_expected(start, len, label);
+        }
+
+        out
+    }
+
     pub fn set_location(&mut self, location: Location) {
         (*self.location) = location;
         if *self.location > self.error.location {
@@ -324,11 +1338,11 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
     }
 
     pub fn look_ahead(&self) -> &TokenBuf<Cx::LookAhead> {
-        &self.look_ahead
+        self.look_ahead
     }
 
     pub fn look_ahead_mut(&mut self) -> &mut TokenBuf<Cx::LookAhead> {
-        &mut self.look_ahead
+        self.look_ahead
     }
 
     pub fn into_parts(self) -> ParseContextParts<'src, 'cx> {
@@ -380,7 +1394,24 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
     where
         Cx: 'next,
     {
-        self.by_ref()
+        let next = next.into();
+        let key = self.memo_key::<T>(*self.location, self.look_ahead, next.unwrap_or_default());
+
+        if let (Some(key), Some(memo)) = (&key, self.memo.as_deref()) {
+            if let Some(entry) = memo.pre_parses.get(key) {
+                self.look_ahead.clone_from(&entry.look_ahead);
+                let result = entry.result;
+                self.trace(|| TraceEvent::Check {
+                    rule: RuleName::of::<T>(),
+                    location: *self.location,
+                    result,
+                });
+                return result.map_err(|location| RuleParseFailed { location });
+            }
+        }
+
+        let out = self
+            .by_ref()
             .update(ParseContextUpdate {
                 error: Some(&mut ParseError {
                     location: Location::MAX,
@@ -388,7 +1419,45 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
                 }),
                 ..default()
             })
-            .pre_parse_inner::<T>(next.into())
+            .pre_parse_inner::<T>(next);
+
+        let location = *self.location;
+        self.trace(|| TraceEvent::Check {
+            rule: RuleName::of::<T>(),
+            location,
+            result: out.as_ref().map(|_| ()).map_err(|err| err.location),
+        });
+
+        if let (Some(key), Some(memo), false) =
+            (key, self.memo.as_deref_mut(), self.state.fatal.is_some())
+        {
+            let entry = PreParseEntry {
+                result: out.as_ref().map(|_| ()).map_err(|err| err.location),
+                look_ahead: self.look_ahead.clone(),
+            };
+            memo.pre_parses.insert(key, entry);
+        }
+
+        out
+    }
+
+    /// Builds a key for the memo table if memoization is enabled.
+    fn memo_key<T: Rule>(
+        &self,
+        location: Location,
+        look_ahead: &TokenBuf<Cx::LookAhead>,
+        next: &RuleType<Cx>,
+    ) -> Option<MemoKey<Cx::LookAhead>> {
+        self.memo.as_ref()?;
+        Some(MemoKey::new(
+            TypeId::of::<T>(),
+            location,
+            self.src,
+            self.prefer_continue,
+            self.mode_id(),
+            look_ahead,
+            next.chain_id(),
+        ))
     }
 
     pub fn record_error<'next, T: Rule>(
@@ -401,29 +1470,114 @@ impl<'src, 'cx, Cx: CxType> ParseContext<'src, 'cx, Cx> {
         self.by_ref().pre_parse_inner::<T>(next.into())
     }
 
+    /// Parses `T` from `start` without affecting the state of this context, other than errors,
+    /// returning where the parse ended.
     pub(crate) fn isolated_parse<T: Rule>(
         &mut self,
         start: impl Into<Option<Location>>,
         next: &RuleType<Cx>,
-    ) -> RuleParseResult<(T, Location)> {
-        let mut start = start.into().unwrap_or(*self.location);
+    ) -> RuleParseResult<Location> {
+        self.isolated_parse_cut::<T>(start.into(), next).0
+    }
+
+    /// Like [`isolated_parse`](Self::isolated_parse), but succeeds with `None` if `T` fails after
+    /// passing a [`Cut`](crate::ast::Cut), since that commits to it.
+    pub(crate) fn isolated_parse_until_cut<T: Rule>(
+        &mut self,
+        start: impl Into<Option<Location>>,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<Option<Location>> {
+        match self.isolated_parse_cut::<T>(start.into(), next) {
+            (Err(_), true) if !self.is_aborted() => Ok(None),
+            (result, _) => result.map(Some),
+        }
+    }
+
+    /// Records that a [`Cut`](crate::ast::Cut) was passed, if it's within a speculative parse.
+    pub(crate) fn pass_cut(&mut self) {
+        if self.state.speculative > 0 {
+            self.state.cut = true;
+        }
+    }
 
-        let mut look_ahead = if start == *self.location {
+    /// Runs an isolated parse, also returning whether it passed a [`Cut`](crate::ast::Cut).
+    fn isolated_parse_cut<T: Rule>(
+        &mut self,
+        start: Option<Location>,
+        next: &RuleType<Cx>,
+    ) -> (RuleParseResult<Location>, bool) {
+        let mark = self.token_mark();
+        let outer_cut = core::mem::replace(&mut self.state.cut, false);
+        self.state.speculative += 1;
+        let out = self.isolated_parse_inner::<T>(start, next);
+        self.state.speculative -= 1;
+        let cut = core::mem::replace(&mut self.state.cut, outer_cut);
+        self.rollback_tokens(mark);
+        (out, cut)
+    }
+
+    fn isolated_parse_inner<T: Rule>(
+        &mut self,
+        start: Option<Location>,
+        next: &RuleType<Cx>,
+    ) -> RuleParseResult<Location> {
+        let mut location = start.unwrap_or(*self.location);
+
+        let mut look_ahead = if location == *self.location {
             self.look_ahead.clone()
         } else {
-            default()
+            self.look_ahead.cleared()
         };
 
-        let out = T::parse(
+        let Some(key) = self.memo_key::<T>(location, &look_ahead, next) else {
+            T::parse(
+                self.by_ref().update(ParseContextUpdate {
+                    look_ahead: Some(&mut look_ahead),
+                    location: Some(&mut location),
+                    diagnostics: Some(&mut Vec::new()),
+                    ..default()
+                }),
+                next,
+            )?;
+
+            return Ok(location);
+        };
+
+        if let Some(entry) = self.memo.as_deref().and_then(|memo| memo.parses.get(&key)) {
+            self.error.merge(&entry.error);
+            self.state.cut = entry.cut;
+            return entry
+                .result
+                .map_err(|location| RuleParseFailed { location });
+        }
+
+        let mut error = ParseError::default();
+        let result = T::parse(
             self.by_ref().update(ParseContextUpdate {
                 look_ahead: Some(&mut look_ahead),
-                location: Some(&mut start),
+                location: Some(&mut location),
+                error: Some(&mut error),
+                diagnostics: Some(&mut Vec::new()),
                 ..default()
             }),
             next,
-        )?;
+        )
+        .map(|_| location)
+        .map_err(|err| err.location);
+
+        self.error.merge(&error);
+        if let (Some(memo), false) = (self.memo.as_deref_mut(), self.state.fatal.is_some()) {
+            memo.parses.insert(
+                key,
+                ParseEntry {
+                    result,
+                    error,
+                    cut: self.state.cut,
+                },
+            );
+        }
 
-        Ok((out, start))
+        result.map_err(|location| RuleParseFailed { location })
     }
 }
 
@@ -436,34 +1590,54 @@ pub struct ParseContextParts<'src, 'cx> {
 }
 
 pub trait TokenBufData:
-    Debug + Copy + AsRef<[Option<AnyToken>]> + AsMut<[Option<AnyToken>]> + Ord + Hash + 'static
+    Debug + Clone + AsRef<[Option<AnyToken>]> + AsMut<[Option<AnyToken>]> + Ord + Hash + 'static
 {
-    fn init_data() -> Self;
+    /// Creates an empty buffer, holding `len` tokens if its length isn't fixed by the type.
+    fn init_data(len: usize) -> Self;
 }
 
 impl<const LEN: usize> TokenBufData for [Option<AnyToken>; LEN] {
-    fn init_data() -> Self {
+    fn init_data(_: usize) -> Self {
         [None; LEN]
     }
 }
 
+/// A buffer whose length is chosen at runtime, so the same grammar can be used with any
+/// lookahead.
+impl TokenBufData for Vec<Option<AnyToken>> {
+    fn init_data(len: usize) -> Self {
+        alloc::vec![None; len]
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TokenBuf<A: TokenBufData> {
     data: A,
 }
 
 impl<A: TokenBufData> TokenBuf<A> {
+    pub fn new(len: usize) -> Self {
+        Self {
+            data: A::init_data(len),
+        }
+    }
+
+    /// Returns an empty buffer of the same length.
+    pub fn cleared(&self) -> Self {
+        Self::new(self.len())
+    }
+
     pub fn shift(&mut self) -> Option<AnyToken> {
         let token = self.first_mut()?.take();
         self.rotate_left(1);
         token
     }
 }
+
+/// An empty buffer, which holds no tokens if its length isn't fixed by the type.
 impl<A: TokenBufData> Default for TokenBuf<A> {
     fn default() -> Self {
-        Self {
-            data: A::init_data(),
-        }
+        Self::new(0)
     }
 }
 
@@ -495,15 +1669,82 @@ impl<A: TokenBufData, S: SliceIndex<[Option<AnyToken>]>> IndexMut<S> for TokenBu
     }
 }
 
+/// Something that would have allowed parsing to continue at the location of a [`ParseError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Expected {
+    Token(&'static TokenType),
+    /// A rule that reports itself as a unit, e.g. "expression".
+    Label(&'static str),
+}
+
+impl Expected {
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Expected::Token(token_type) => token_type.display_name(),
+            Expected::Label(label) => label,
+        }
+    }
+}
+
+impl From<&'static TokenType> for Expected {
+    fn from(token_type: &'static TokenType) -> Self {
+        Expected::Token(token_type)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// The input didn't match what was expected.
+    #[default]
+    Unexpected,
+    /// A rule recursed into itself without consuming any input, e.g. `Expr = Expr '+' Term`.
+    ///
+    /// `cycle` lists the rules involved, starting and ending with the recursive rule.
+    LeftRecursion { cycle: Vec<&'static str> },
+    /// An item of a repetition, e.g. in a [`Vec`], matched without consuming any input, so it
+    /// would repeat forever.
+    EmptyRepetition,
+    /// A rule was nested within itself more deeply than allowed by [`ParseOptions::max_depth`].
+    NestingTooDeep { max_depth: usize },
+    /// The parse took more steps than allowed by [`ParseOptions::fuel`].
+    OutOfFuel,
+    /// The parse was stopped by the callback given to [`ParseOptions::cancel_when`].
+    Cancelled,
+    /// The input at `range` was parsed, but rejected when building the tree, e.g. a number that's
+    /// too large for its type.
+    Invalid {
+        range: LocationRange,
+        message: String,
+    },
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ParseError<'src> {
+    pub kind: ParseErrorKind,
     pub location: Location,
+    /// The source the error occurred in, once the parse has finished.
+    pub src: Option<&'src str>,
     pub actual: &'src str,
-    pub expected: Vec<&'static TokenType>,
+    pub expected: Vec<Expected>,
 }
 
-impl ParseError<'_> {
-    pub fn add_expected(&mut self, location: Location, token_type: &'static TokenType) {
+impl<'src> ParseError<'src> {
+    pub fn line_column(&self) -> LineColumn {
+        self.line_column_with(&LineIndex::new(self.src.unwrap_or_default()))
+    }
+
+    /// Like [`line_column`](Self::line_column), but reuses an existing index of the source.
+    pub fn line_column_with(&self, index: &LineIndex) -> LineColumn {
+        index.line_column(self.location)
+    }
+
+    pub fn add_expected(&mut self, location: Location, expected: impl Into<Expected>) {
+        // a rejected value is reported instead of anything that was expected
+        if self.is_invalid() {
+            return;
+        }
+        let expected = expected.into();
         match location.cmp(&self.location) {
             Ordering::Less => return,
             Ordering::Equal => {}
@@ -512,8 +1753,16 @@ impl ParseError<'_> {
                 self.expected.clear();
             }
         }
-        if !self.expected.contains(&token_type) {
-            self.expected.push(token_type);
+        if !self.expected.contains(&expected) {
+            self.expected.push(expected);
+        }
+    }
+
+    /// Replaces everything expected at `location` since the first `len` expectations with `label`.
+    pub(crate) fn replace_expected(&mut self, location: Location, len: usize, label: &'static str) {
+        if self.location == location {
+            self.expected.truncate(len);
+            self.add_expected(location, Expected::Label(label));
         }
     }
 
@@ -521,7 +1770,34 @@ impl ParseError<'_> {
         self.expected.clear();
     }
 
-    pub fn expected(&self) -> impl Iterator<Item = &'static TokenType> + '_ {
+    /// Combines the expectations of `other` into `self`, keeping whichever location is furthest,
+    /// or the value rejected by either of them.
+    pub fn merge(&mut self, other: &ParseError) {
+        if self.is_invalid() {
+            return;
+        }
+        if other.is_invalid() {
+            self.kind = other.kind.clone();
+            self.location = other.location;
+            self.expected.clear();
+            return;
+        }
+
+        if other.location > self.location {
+            self.location = other.location;
+            self.expected.clear();
+        }
+
+        for &expected in &other.expected {
+            self.add_expected(other.location, expected);
+        }
+    }
+
+    fn is_invalid(&self) -> bool {
+        matches!(self.kind, ParseErrorKind::Invalid { .. })
+    }
+
+    pub fn expected(&self) -> impl Iterator<Item = Expected> + '_ {
         self.expected.iter().copied()
     }
 }
@@ -1,13 +1,16 @@
+use alloc::format;
 use core::{
     any::{Any, TypeId},
     cmp::Ordering,
-    fmt::{self, Debug, Formatter},
+    fmt::{self, Debug, Display, Formatter},
     ptr,
+    str::FromStr,
 };
 
 use crate::{
     ast::{Discard, Token, TransformRule},
-    parse::{CxType, Location, LocationRange},
+    internal_prelude::*,
+    parse::{Location, LocationRange},
     utils::simple_name,
 };
 
@@ -22,6 +25,12 @@ pub trait TokenDef: Any {
         Self::name()
     }
 
+    /// A regex that matches wherever this token can be lexed, at least, so that many tokens can
+    /// be ruled out at once by [`Lexer`](crate::lexer::Lexer).
+    fn pattern() -> Option<String> {
+        None
+    }
+
     fn print_debug(src: &str, range: LocationRange, f: &mut Formatter) -> fmt::Result {
         write!(
             f,
@@ -36,6 +45,51 @@ pub trait TokenDef: Any {
     }
 }
 
+/// A token that holds a value converted from its text, like `struct Int(u64)` declared with
+/// [`define_token!`](crate::define_token).
+pub trait ValueTokenDef: TokenDef {
+    type Value: Debug;
+
+    /// Converts the text of the token, or describes why it's invalid.
+    fn convert(text: &str) -> Result<Self::Value, String>;
+}
+
+#[doc(hidden)]
+pub fn exact_pattern(pattern: &str, ignore_case: bool) -> String {
+    let pattern = regex::escape(pattern);
+    match ignore_case {
+        true => format!("(?i:{pattern})"),
+        false => pattern,
+    }
+}
+
+#[doc(hidden)]
+pub fn convert_from_str<T: FromStr>(text: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    convert_result(text.parse())
+}
+
+#[doc(hidden)]
+pub fn convert_result<T, E: Display>(result: Result<T, E>) -> Result<T, String> {
+    result.map_err(|err| err.to_string())
+}
+
+/// Rejects `range` if any of the `reserved` tokens would lex exactly the same text, e.g. to stop
+/// an identifier from matching a keyword.
+#[doc(hidden)]
+pub fn reject_reserved(
+    reserved: &[fn(&str, Location) -> Option<LocationRange>],
+    src: &str,
+    range: LocationRange,
+) -> Option<LocationRange> {
+    let is_reserved = reserved
+        .iter()
+        .any(|try_lex| try_lex(src, range.start) == Some(range));
+    (!is_reserved).then_some(range)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct AnyToken {
     pub token_type: &'static TokenType,
@@ -44,6 +98,8 @@ pub struct AnyToken {
 
 pub struct TokenType {
     name: fn() -> &'static str,
+    display_name: fn() -> &'static str,
+    pattern: fn() -> Option<String>,
     token_id: fn() -> TypeId,
     try_lex: fn(&str, Location) -> Option<LocationRange>,
 }
@@ -84,6 +140,8 @@ impl TokenType {
     pub const fn of<T: TokenDef>() -> &'static Self {
         &Self {
             name: T::name,
+            display_name: T::display_name,
+            pattern: T::pattern,
             token_id: TypeId::of::<T>,
             try_lex: T::try_lex,
         }
@@ -93,11 +151,19 @@ impl TokenType {
         (self.name)()
     }
 
+    pub fn display_name(&self) -> &'static str {
+        (self.display_name)()
+    }
+
+    pub fn pattern(&self) -> Option<String> {
+        (self.pattern)()
+    }
+
     pub fn token_id(&self) -> TypeId {
         (self.token_id)()
     }
 
-    pub fn try_lex<Cx: CxType>(&'static self, src: &str, location: Location) -> Option<AnyToken> {
+    pub fn try_lex(&'static self, src: &str, location: Location) -> Option<AnyToken> {
         Some(AnyToken {
             token_type: self,
             range: (self.try_lex)(src, location)?,
@@ -132,12 +198,17 @@ impl TransformRule for Eof {
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _define_token {
-    (@try_lex $Name:ident (regex = $pattern:literal $(, capture = $cap:literal)? $(,)?)) => {
+    (@try_lex [$(#$attr:tt)*] $Name:ident (regex = $pattern:literal $(, capture = $cap:literal)? $(,)?)) => {
         fn try_lex(src: &str, location: $crate::parse::Location) -> Option<$crate::parse::LocationRange> {
             $crate::_lazy_regex! {
                 static ref PATTERN => ::core::concat!(r"\A", $pattern);
             }
-            $crate::parse::lex_regex(&PATTERN, 0 $(+ $cap)?, src, location)
+            let range = $crate::parse::lex_regex(&PATTERN, 0 $(+ $cap)?, src, location)?;
+            $crate::_token_reserved! { src, range; $(#$attr)* }
+        }
+
+        fn pattern() -> Option<$crate::String> {
+            Some($crate::String::from($pattern))
         }
 
         fn name() -> &'static str {
@@ -151,11 +222,61 @@ macro_rules! _define_token {
             ))
         }
     };
-    (@try_lex $Name:ident (exact = $pattern:literal)) => {
+    (@try_lex [$(#$attr:tt)*] $Name:ident (
+        keyword = $pattern:literal $(, ident_char = $class:literal)?, case_insensitive $(,)?
+    )) => {
+        $crate::_define_token! { @keyword [$(#$attr)*] $Name $pattern true $($class)? }
+    };
+    (@try_lex [$(#$attr:tt)*] $Name:ident (
+        keyword = $pattern:literal $(, ident_char = $class:literal)? $(,)?
+    )) => {
+        $crate::_define_token! { @keyword [$(#$attr)*] $Name $pattern false $($class)? }
+    };
+    (@try_lex [$(#$attr:tt)*] $Name:ident (exact = $pattern:literal, case_insensitive $(,)?)) => {
+        fn try_lex(src: &str, location: $crate::parse::Location) -> Option<$crate::parse::LocationRange> {
+            let range = $crate::parse::lex_exact_ignore_case($pattern, src, location)?;
+            $crate::_token_reserved! { src, range; $(#$attr)* }
+        }
+
+        fn pattern() -> Option<$crate::String> {
+            Some($crate::token::exact_pattern($pattern, true))
+        }
+
+        $crate::_define_token! { @exact_names $Name $pattern }
+    };
+    (@try_lex [$(#$attr:tt)*] $Name:ident (exact = $pattern:literal $(,)?)) => {
+        fn try_lex(src: &str, location: $crate::parse::Location) -> Option<$crate::parse::LocationRange> {
+            let range = $crate::parse::lex_exact($pattern, src, location)?;
+            $crate::_token_reserved! { src, range; $(#$attr)* }
+        }
+
+        fn pattern() -> Option<$crate::String> {
+            Some($crate::token::exact_pattern($pattern, false))
+        }
+
+        $crate::_define_token! { @exact_names $Name $pattern }
+    };
+    (@keyword [$(#$attr:tt)*] $Name:ident $pattern:literal $ignore_case:literal $($class:literal)?) => {
         fn try_lex(src: &str, location: $crate::parse::Location) -> Option<$crate::parse::LocationRange> {
-            $crate::parse::lex_exact($pattern, src, location)
+            let ident_char: Option<&$crate::Regex> = None;
+            $(
+                $crate::_lazy_regex! {
+                    static ref IDENT_CHAR => ::core::concat!(r"\A(?:", $class, ")");
+                }
+                let ident_char = Some(&*IDENT_CHAR);
+            )?
+            let range =
+                $crate::parse::lex_keyword($pattern, $ignore_case, ident_char, src, location)?;
+            $crate::_token_reserved! { src, range; $(#$attr)* }
         }
 
+        fn pattern() -> Option<$crate::String> {
+            Some($crate::token::exact_pattern($pattern, $ignore_case))
+        }
+
+        $crate::_define_token! { @exact_names $Name $pattern }
+    };
+    (@exact_names $Name:ident $pattern:literal) => {
         fn name() -> &'static str {
             ::core::concat!("'", $pattern, "'")
         }
@@ -171,16 +292,37 @@ macro_rules! _define_token {
             f.write_str(::core::stringify!($pattern))
         }
     };
-    (@impl_rule $Name:ident ($Ty:ty)) => {
-        impl $crate::ast::TransformRule for $name {
-            type Inner = $crate::ast::DualParse<$crate::ast::Discard<$crate::ast::Token<$Name>>, $Ty>>;
+    // tokens that match in any case are shown as they're written in the pattern, rather than by
+    // a name whose casing could be mistaken for the one that's required
+    (@display_name $Name:ident (exact = $pattern:literal, case_insensitive $(,)?)) => {
+        ::core::concat!("'", $pattern, "'")
+    };
+    (@display_name $Name:ident (
+        keyword = $pattern:literal $(, ident_char = $class:literal)?, case_insensitive $(,)?
+    )) => {
+        ::core::concat!("'", $pattern, "'")
+    };
+    (@display_name $Name:ident $pattern:tt) => {
+        ::core::stringify!($Name)
+    };
+    (@impl_rule [$(#$attr:tt)*] $Name:ident ($Ty:ty)) => {
+        impl $crate::token::ValueTokenDef for $Name {
+            type Value = $Ty;
+
+            fn convert(text: &str) -> ::core::result::Result<$Ty, $crate::String> {
+                $crate::_token_convert! { $Ty; text; $(#$attr)* }
+            }
+        }
+
+        impl $crate::ast::TransformRule for $Name {
+            type Inner = $crate::ast::TokenValue<$Name>;
 
             fn from_inner(inner: Self::Inner) -> Self {
-                Self(inner.inner)
+                Self(inner.value)
             }
         }
     };
-    (@impl_rule $Name:ident) => {
+    (@impl_rule [$(#$attr:tt)*] $Name:ident) => {
         impl $crate::ast::TransformRule for $Name {
             type Inner = $crate::ast::Token<$Name>;
 
@@ -197,18 +339,21 @@ macro_rules! _define_token {
             }
         }
     };
-    (@define_struct
-        $(#$attr:tt)*
-        $vis:vis struct $Name:ident ($Ty:ty);
-    ) => {
+    (@define_struct [$(#$kept:tt)*] #[convert $($x:tt)*] $($rest:tt)*) => {
+        $crate::_define_token! { @define_struct [$(#$kept)*] $($rest)* }
+    };
+    (@define_struct [$(#$kept:tt)*] #[reserved $($x:tt)*] $($rest:tt)*) => {
+        $crate::_define_token! { @define_struct [$(#$kept)*] $($rest)* }
+    };
+    (@define_struct [$(#$kept:tt)*] #$attr1:tt $($rest:tt)*) => {
+        $crate::_define_token! { @define_struct [$(#$kept)* #$attr1] $($rest)* }
+    };
+    (@define_struct [$(#$attr:tt)*] $vis:vis struct $Name:ident ($Ty:ty);) => {
         $(#$attr)*
         #[derive(Debug)]
-        $vis struct $Name ($Ty);
+        $vis struct $Name(pub $Ty);
     };
-    (@define_struct
-        $(#$attr:tt)*
-        $vis:vis struct $Name:ident;
-    ) => {
+    (@define_struct [$(#$attr:tt)*] $vis:vis struct $Name:ident;) => {
         $(#$attr)*
         #[derive(Debug)]
         $vis struct $Name { pub range: $crate::parse::LocationRange }
@@ -218,23 +363,55 @@ macro_rules! _define_token {
         $(#$attr:tt)*
         $vis:vis struct $Name:ident $(($Ty:ty))?;
     )*) => {$(
-        $crate::_define_token! {@define_struct
+        $crate::_define_token! {@define_struct []
             $(#$attr)*
             $vis struct $Name $(($Ty))?;
         }
 
         impl $crate::token::TokenDef for $Name {
-            $crate::_define_token! { @try_lex $Name $pattern }
+            $crate::_define_token! { @try_lex [$(#$attr)*] $Name $pattern }
 
             fn display_name() -> &'static str {
-                ::core::stringify!($Name)
+                $crate::_define_token! { @display_name $Name $pattern }
             }
         }
 
-        $crate::_define_token! { @impl_rule $Name $(($Ty:ty))? }
+        $crate::_define_token! { @impl_rule [$(#$attr)*] $Name $(($Ty))? }
     )*};
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _token_convert {
+    ($Ty:ty; $text:ident;) => {
+        $crate::token::convert_from_str::<$Ty>($text)
+    };
+    ($Ty:ty; $text:ident; #[convert($convert:expr $(,)?)] $(#$attr:tt)*) => {
+        $crate::token::convert_result($convert($text))
+    };
+    ($Ty:ty; $text:ident; #$attr1:tt $(#$attr:tt)*) => {
+        $crate::_token_convert! { $Ty; $text; $(#$attr)* }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _token_reserved {
+    ($src:ident, $range:ident;) => {
+        Some($range)
+    };
+    ($src:ident, $range:ident; #[reserved($($Keyword:ty),* $(,)?)] $(#$attr:tt)*) => {
+        $crate::token::reject_reserved(
+            &[$(<$Keyword as $crate::token::TokenDef>::try_lex),*],
+            $src,
+            $range,
+        )
+    };
+    ($src:ident, $range:ident; #$attr1:tt $(#$attr:tt)*) => {
+        $crate::_token_reserved! { $src, $range; $(#$attr)* }
+    };
+}
+
 #[macro_export]
 macro_rules! define_token {
     ($(
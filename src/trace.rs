@@ -0,0 +1,209 @@
+//! Hooks for observing a parse as it happens, to debug how a grammar behaves.
+//!
+//! ```
+//! # use rs_typed_parser::{ParseOptions, trace::TraceLog};
+//! rs_typed_parser::define_token!(
+//!     #[pattern(regex = "[0-9]+")]
+//!     pub struct Digits;
+//! );
+//!
+//! let log = TraceLog::new();
+//! ParseOptions::new()
+//!     .tracer(&log)
+//!     .parse_tree::<Digits, 1>("42")
+//!     .unwrap();
+//! println!("{log}");
+//! ```
+
+use alloc::{format, string::String};
+use core::{
+    cell::{Cell, RefCell},
+    fmt::{self, Debug, Display, Formatter, Write},
+};
+
+use crate::{
+    parse::{Location, LocationRange},
+    token::{AnyToken, TokenType},
+    Rule,
+};
+
+/// The name of a rule, as given by [`Rule::print_name`].
+#[derive(Clone, Copy)]
+pub struct RuleName(fn(&mut Formatter) -> fmt::Result);
+
+impl RuleName {
+    pub fn of<T: Rule>() -> Self {
+        Self(T::print_name)
+    }
+}
+
+impl Display for RuleName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (self.0)(f)
+    }
+}
+
+impl Debug for RuleName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// Something that happened during a parse.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum TraceEvent {
+    /// A rule started being parsed, or pre-parsed, at `location`.
+    Enter {
+        rule: RuleName,
+        location: Location,
+        pre_parse: bool,
+    },
+    /// The rule from the matching [`Enter`](Self::Enter) finished.
+    ///
+    /// On success, `result` holds where parsing continues from, which is where the rule started
+    /// for a pre-parse. Since pre-parsing checks whatever follows the rule before returning, a
+    /// failed pre-parse may be due to a later rule.
+    Exit {
+        rule: RuleName,
+        pre_parse: bool,
+        result: Result<Location, Location>,
+    },
+    /// A rule was checked at `location` to decide whether to parse it, as [`Either`] does for
+    /// each alternative.
+    ///
+    /// [`Either`]: crate::Either
+    Check {
+        rule: RuleName,
+        location: Location,
+        result: Result<(), Location>,
+    },
+    /// A token was lexed at `location`, giving its range if it matched.
+    Lex {
+        token_type: &'static TokenType,
+        location: Location,
+        result: Option<LocationRange>,
+    },
+    /// The lookahead buffer was filled with `token` at index `dist`.
+    LookAhead { dist: usize, token: AnyToken },
+}
+
+/// Receives events from a parse, set with [`ParseOptions::tracer`].
+///
+/// [`ParseOptions::tracer`]: crate::ParseOptions::tracer
+pub trait Tracer {
+    fn event(&self, event: TraceEvent);
+}
+
+impl Debug for dyn Tracer + '_ {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Tracer")
+    }
+}
+
+/// A [`Tracer`] that records events as a log, indented by how deeply rules are nested.
+///
+/// Positions are byte offsets into the source.
+#[derive(Debug, Default)]
+pub struct TraceLog {
+    log: RefCell<String>,
+    depth: Cell<usize>,
+}
+
+impl TraceLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_string(self) -> String {
+        self.log.into_inner()
+    }
+
+    fn write_event(&self, f: &mut String, event: TraceEvent) -> fmt::Result {
+        let outcome = |result: Result<(), Location>| match result {
+            Ok(()) => String::from("ok"),
+            Err(location) => format!("failed @{}", location.position),
+        };
+
+        match event {
+            TraceEvent::Enter {
+                rule,
+                location,
+                pre_parse,
+            } => {
+                let action = if pre_parse { "pre-parse" } else { "parse" };
+                writeln!(f, "{action} {rule} @{}", location.position)
+            }
+            TraceEvent::Exit {
+                rule,
+                pre_parse: true,
+                result,
+            } => writeln!(f, "{} {rule}", outcome(result.map(|_| ()))),
+            TraceEvent::Exit { rule, result, .. } => match result {
+                Ok(end) => writeln!(f, "ok {rule} to @{}", end.position),
+                Err(_) => writeln!(f, "{} {rule}", outcome(result.map(|_| ()))),
+            },
+            TraceEvent::Check {
+                rule,
+                location,
+                result,
+            } => writeln!(
+                f,
+                "check {rule} @{}: {}",
+                location.position,
+                outcome(result)
+            ),
+            TraceEvent::Lex {
+                token_type,
+                location,
+                result,
+            } => match result {
+                Some(range) => writeln!(
+                    f,
+                    "lex {} @{}: {}..{}",
+                    token_type.display_name(),
+                    location.position,
+                    range.start.position,
+                    range.end.position,
+                ),
+                None => writeln!(
+                    f,
+                    "lex {} @{}: no match",
+                    token_type.display_name(),
+                    location.position,
+                ),
+            },
+            TraceEvent::LookAhead { dist, token } => writeln!(
+                f,
+                "look-ahead[{dist}] = {} {}..{}",
+                token.token_type.display_name(),
+                token.range.start.position,
+                token.range.end.position,
+            ),
+        }
+    }
+}
+
+impl Tracer for TraceLog {
+    fn event(&self, event: TraceEvent) {
+        if let TraceEvent::Exit { .. } = event {
+            self.depth.set(self.depth.get().saturating_sub(1));
+        }
+
+        let mut log = self.log.borrow_mut();
+        for _ in 0..self.depth.get() {
+            log.push_str("  ");
+        }
+        let _ = self.write_event(&mut log, event);
+
+        if let TraceEvent::Enter { .. } = event {
+            self.depth.set(self.depth.get() + 1);
+        }
+    }
+}
+
+impl Display for TraceLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.log.borrow())
+    }
+}
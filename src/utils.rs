@@ -1,4 +1,5 @@
 use core::fmt;
+use core::hash::{Hash, Hasher};
 use core::ops::ControlFlow::{self, Break, Continue};
 
 pub(crate) fn default<T: Default>() -> T {
@@ -19,6 +20,34 @@ impl<F: Fn(&mut fmt::Formatter) -> fmt::Result> fmt::Display for DebugFn<F> {
     }
 }
 
+/// Combines `value` into the id `seed`, so the id of a sequence can be built one item at a time
+/// instead of hashing the whole sequence every time it's compared.
+pub(crate) fn combine_id(seed: u64, value: impl Hash) -> u64 {
+    let mut hasher = IdHasher(seed);
+    value.hash(&mut hasher);
+    hasher.0
+}
+
+struct IdHasher(u64);
+
+impl Hasher for IdHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut buf = [0; 8];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            self.write_u64(u64::from_le_bytes(buf));
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
 pub(crate) fn simple_name<T: ?Sized>() -> &'static str {
     let mut name = core::any::type_name::<T>();
     if let Some((first, _)) = name.split_once('<') {
@@ -32,10 +61,6 @@ pub(crate) fn simple_name<T: ?Sized>() -> &'static str {
     name
 }
 
-pub(crate) fn run<R>(f: impl FnOnce() -> R) -> R {
-    f()
-}
-
 pub(crate) fn try_run<T, E>(f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
     f()
 }
@@ -51,38 +76,6 @@ pub(crate) trait MyTry: Sized {
     fn into_ctrl(self) -> ControlFlow<Self::Break, Self::Continue>;
     fn from_ctrl(ctrl: ControlFlow<Self::Break, Self::Continue>) -> Self;
 
-    fn from_continue(c: Self::Continue) -> Self {
-        Self::from_ctrl(Continue(c))
-    }
-
-    fn from_break(b: Self::Break) -> Self {
-        Self::from_ctrl(Break(b))
-    }
-
-    fn map<C>(self, f: impl FnOnce(Self::Continue) -> C) -> WithContinue<Self, C> {
-        match self.into_ctrl() {
-            Continue(c) => MyTry::from_continue(f(c)),
-            Break(b) => MyTry::from_break(b),
-        }
-    }
-
-    fn flat_map<C>(
-        self,
-        f: impl FnOnce(Self::Continue) -> WithContinue<Self, C>,
-    ) -> WithContinue<Self, C> {
-        match self.into_ctrl() {
-            Continue(c) => f(c),
-            Break(b) => MyTry::from_break(b),
-        }
-    }
-
-    fn continue_also(self, f: impl FnOnce(&mut Self::Continue)) -> Self {
-        self.map(|mut c| {
-            f(&mut c);
-            c
-        })
-    }
-
     fn break_also(self, f: impl FnOnce(&mut Self::Break)) -> Self {
         let mut ctrl = self.into_ctrl();
         if let Break(ref mut b) = ctrl {
@@ -90,60 +83,8 @@ pub(crate) trait MyTry: Sized {
         }
         Self::from_ctrl(ctrl)
     }
-
-    fn continue_also_with<R>(
-        self,
-        f: impl FnOnce(&mut Self::Continue) -> R,
-    ) -> WithContinue<Self, (Self::Continue, R)> {
-        self.map(|mut c| {
-            let out = f(&mut c);
-            (c, out)
-        })
-    }
-    fn map_break<B>(self, f: impl FnOnce(Self::Break) -> B) -> Self::WithBreak<B>
-    where
-        Self: MapBreak,
-    {
-        match self.into_ctrl() {
-            Continue(c) => MyTry::from_continue(c),
-            Break(b) => MyTry::from_break(f(b)),
-        }
-    }
-
-    fn flat_map_break<B>(
-        self,
-        f: impl FnOnce(Self::Break) -> Self::WithBreak<B>,
-    ) -> Self::WithBreak<B>
-    where
-        Self: MapBreak,
-    {
-        match self.into_ctrl() {
-            Continue(c) => MyTry::from_continue(c),
-            Break(b) => f(b),
-        }
-    }
-
-    fn break_also_with<R>(
-        self,
-        f: impl FnOnce(&mut Self::Continue) -> R,
-    ) -> WithContinue<Self, (Self::Continue, R)>
-    where
-        Self: MapBreak,
-    {
-        self.map(|mut c| {
-            let out = f(&mut c);
-            (c, out)
-        })
-    }
 }
 
-pub(crate) trait MapBreak: MyTry {
-    type WithBreak<B>: MapBreak<WithBreak<Self::Break> = Self, Continue = Self::Continue, Break = B>;
-}
-
-pub(crate) type WithContinue<T, C> = <<T as MyTry>::Kind as MyTryKind>::WithContinue<C>;
-pub(crate) type WithBreak<T, B> = <T as MapBreak>::WithBreak<B>;
-
 impl MyTryKind for Option<()> {
     type WithContinue<T> = Option<T>;
 }
@@ -163,10 +104,6 @@ impl<T> MyTry for Option<T> {
             Break(()) => None,
         }
     }
-
-    fn map<C>(self, f: impl FnOnce(Self::Continue) -> C) -> WithContinue<Self, C> {
-        self.map(f)
-    }
 }
 
 impl<E> MyTryKind for Result<(), E> {
@@ -187,14 +124,6 @@ impl<T, E> MyTry for Result<T, E> {
             Break(e) => Err(e),
         }
     }
-
-    fn map<C>(self, f: impl FnOnce(Self::Continue) -> C) -> WithContinue<Self, C> {
-        self.map(f)
-    }
-}
-
-impl<T, E> MapBreak for Result<T, E> {
-    type WithBreak<B> = Result<T, B>;
 }
 
 impl<B> MyTryKind for ControlFlow<B> {
@@ -214,25 +143,3 @@ impl<B, C> MyTry for ControlFlow<B, C> {
         ctrl
     }
 }
-
-impl<B, C> MapBreak for ControlFlow<B, C> {
-    type WithBreak<B2> = ControlFlow<B2, C>;
-}
-
-pub(crate) trait FunctionalExt: Sized {
-    fn then_do<R>(self, f: impl FnOnce(Self) -> R) -> R {
-        f(self)
-    }
-
-    fn also_with<R>(mut self, f: impl FnOnce(&mut Self) -> R) -> (Self, R) {
-        let value = f(&mut self);
-        (self, value)
-    }
-
-    fn also(mut self, f: impl FnOnce(&mut Self)) -> Self {
-        f(&mut self);
-        self
-    }
-}
-
-impl<T> FunctionalExt for T {}
@@ -0,0 +1,21 @@
+#![allow(non_camel_case_types)]
+
+use std::marker::PhantomData;
+
+use rs_typed_parser::{
+    ast::{transform::TransformWithSource, Token},
+    TokenDef,
+};
+
+/// Reads a `T` token as a number.
+pub struct number<T> {
+    _t: PhantomData<T>,
+}
+
+impl<T: TokenDef> TransformWithSource<u32> for number<T> {
+    type Input = Token<T>;
+
+    fn transform(input: Self::Input, src: &str) -> u32 {
+        input.range.slice(src).parse().unwrap()
+    }
+}
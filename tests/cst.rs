@@ -0,0 +1,150 @@
+use rs_typed_parser::{
+    ast::{DelimitedList, Discard, DualParse, Ignore, Spanned, Token},
+    parse::LocationRange,
+    parse_cst, Either, ParseOptions,
+};
+
+type Trivia = Ignore<Either<Space, Comment>>;
+
+rs_typed_parser::define_rule!(
+    pub struct List {
+        l_bracket: Discard<LBracket>,
+        items: DelimitedList<Item, Comma>,
+        r_bracket: Discard<RBracket>,
+    }
+    pub enum Item {
+        Word { word: Word },
+        List { list: Box<List> },
+    }
+    pub struct Word {
+        inner: DualParse<Token<Ident>, Parts>,
+    }
+    pub struct Parts {
+        parts: DelimitedList<Part, Underscore, false>,
+    }
+    pub struct Groups {
+        groups: Vec<Spanned<Group>>,
+    }
+    pub struct Group {
+        l_bracket: LBracket,
+        words: Vec<Token<Ident>>,
+        r_bracket: RBracket,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "[")]
+    pub struct LBracket;
+    #[pattern(exact = "]")]
+    pub struct RBracket;
+    #[pattern(exact = ",")]
+    pub struct Comma;
+    #[pattern(exact = "_")]
+    pub struct Underscore;
+    #[pattern(regex = "[a-z_]+")]
+    pub struct Ident;
+    #[pattern(regex = "[a-z]+")]
+    pub struct Part;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+    #[pattern(regex = r"//[^\n]*")]
+    pub struct Comment;
+);
+
+#[test]
+pub fn round_trip_test() {
+    let inputs = [
+        "[a,b]",
+        "  [ a , b_c ]  ",
+        "[\n  a, // first\n  [b, c,],\n] // done\n\n",
+    ];
+
+    for src in inputs {
+        let cst = ParseOptions::new()
+            .trivia::<Trivia>()
+            .parse_cst::<List, 1>(src)
+            .unwrap();
+        assert_eq!(cst.to_source(src), src);
+    }
+}
+
+#[test]
+pub fn trivia_test() {
+    let src = "[\n  a, // first\n  b_c\n] // done\n";
+    let cst = ParseOptions::new()
+        .trivia::<Trivia>()
+        .parse_cst::<List, 1>(src)
+        .unwrap();
+
+    // the brackets and commas are discarded, so they're trivia
+    let tokens: Vec<_> = cst
+        .tokens
+        .iter()
+        .map(|token| {
+            (
+                token.leading.slice(src),
+                token.token.range.slice(src),
+                token.trailing.slice(src),
+            )
+        })
+        .collect();
+    assert_eq!(
+        tokens,
+        [
+            ("[\n  ", "a", ", // first\n"),
+            ("  ", "b_c", "\n"),
+            ("] // done\n", "", ""),
+        ]
+    );
+
+    let Item::Word { word } = &cst.ast.items.items[1] else {
+        panic!("expected a word");
+    };
+    let token = cst.token(word.inner.outer.range).unwrap();
+    assert_eq!(token.full_range().slice(src), "  b_c\n");
+}
+
+#[test]
+pub fn no_trivia_test() {
+    let src = "[a,[b]]";
+    let cst = parse_cst::<List, 1>(src).unwrap();
+    assert_eq!(cst.tokens.len(), 3);
+    assert_eq!(cst.to_source(src), src);
+    assert!(parse_cst::<List, 1>("[a, b]").is_err());
+}
+
+#[test]
+pub fn node_trivia_test() {
+    let src = "[a b] // one\n  [] [c]\n";
+    let cst = ParseOptions::new()
+        .trivia::<Trivia>()
+        .parse_cst::<Groups, 1>(src)
+        .unwrap();
+
+    let nodes: Vec<_> = cst
+        .ast
+        .groups
+        .iter()
+        .map(|group| cst.node(group.span).unwrap())
+        .collect();
+    let trivia: Vec<_> = nodes
+        .iter()
+        .map(|node| (node.leading().slice(src), node.trailing().slice(src)))
+        .collect();
+    assert_eq!(trivia, [("", " // one\n"), ("  ", " "), ("", "\n")]);
+    assert_eq!(nodes[0].tokens.len(), 4);
+
+    // the nodes and their trivia cover the source, up to the end of the file
+    let source: String = nodes
+        .iter()
+        .map(|node| node.full_range().slice(src))
+        .collect();
+    assert_eq!(source, src);
+
+    let empty = cst.ast.groups[1].span.end;
+    let empty = LocationRange {
+        start: empty,
+        end: empty,
+    };
+    assert!(cst.node(empty).is_none());
+}
@@ -0,0 +1,168 @@
+use rs_typed_parser::{
+    ast::{Backtrack, Cut, Ignore, Recover},
+    ParseOptions,
+};
+
+rs_typed_parser::define_rule!(
+    pub struct Items {
+        items: Vec<Item>,
+    }
+    pub enum Item {
+        Fn {
+            fn_kw: Fn,
+            cut: Cut,
+            name: Ident,
+            l_paren: LParen,
+            r_paren: RParen,
+            semi: Semi,
+        },
+        Call {
+            name: Ident,
+            l_paren: LParen,
+            r_paren: RParen,
+            semi: Semi,
+        },
+    }
+    pub struct UncutItems {
+        items: Vec<UncutItem>,
+    }
+    pub enum UncutItem {
+        Fn {
+            fn_kw: Fn,
+            name: Ident,
+            l_paren: LParen,
+            r_paren: RParen,
+            semi: Semi,
+        },
+        Call {
+            name: Ident,
+            l_paren: LParen,
+            r_paren: RParen,
+            semi: Semi,
+        },
+    }
+    pub struct Decls {
+        decls: Vec<Decl>,
+    }
+    pub enum Decl {
+        Fn {
+            fn_kw: Fn,
+            name: Ident,
+            l_paren: LParen,
+            cut: Cut,
+            r_paren: RParen,
+        },
+        FnDecl {
+            fn_kw: Fn,
+            name: Ident,
+            semi: Semi,
+        },
+    }
+    pub enum Stmt {
+        Item { item: Item },
+        Bare { fn_kw: Fn, semi: Semi },
+    }
+    pub enum BacktrackingStmt {
+        Item { item: Backtrack<Item> },
+        Bare { fn_kw: Fn, semi: Semi },
+    }
+    pub enum BacktrackingUncutStmt {
+        Item { item: Backtrack<UncutItem> },
+        Bare { fn_kw: Fn, semi: Semi },
+    }
+    pub struct RecoveringItems {
+        items: Vec<Recover<Item, Semi>>,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "fn")]
+    pub struct Fn;
+    #[pattern(exact = "(")]
+    pub struct LParen;
+    #[pattern(exact = ")")]
+    pub struct RParen;
+    #[pattern(exact = ";")]
+    pub struct Semi;
+    #[pattern(regex = "[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+fn options() -> ParseOptions<'static> {
+    ParseOptions::new().trivia::<Ignore<Space>>()
+}
+
+#[test]
+pub fn cut_test() {
+    let items = options().parse_tree::<Items, 2>("a(); fn b();").unwrap();
+    assert!(matches!(
+        items.items[..],
+        [Item::Call { .. }, Item::Fn { .. }]
+    ));
+
+    // without a cut, `fn` falls back to being the name of a call
+    let items = options().parse_tree::<UncutItems, 2>("fn ();").unwrap();
+    assert!(matches!(items.items[..], [UncutItem::Call { .. }]));
+
+    // with it, the function is committed to once `fn` is seen, so the error is where it fails
+    let err = options().parse_tree::<Items, 2>("fn ();").unwrap_err();
+    assert_eq!(err.location.position, 3);
+    assert_eq!(
+        err.expected().map(|e| e.display_name()).collect::<Vec<_>>(),
+        ["Ident"]
+    );
+}
+
+#[test]
+pub fn cut_after_prefix_test() {
+    // alternatives are still tried until the cut is reached
+    let decls = options().parse_tree::<Decls, 3>("fn a;fn b()").unwrap();
+    assert!(matches!(
+        decls.decls[..],
+        [Decl::FnDecl { .. }, Decl::Fn { .. }]
+    ));
+
+    let err = options().parse_tree::<Decls, 3>("fn a(;").unwrap_err();
+    assert_eq!(err.location.position, 5);
+}
+
+#[test]
+pub fn cut_recover_test() {
+    // a failure after a cut is still an ordinary error that can be recovered from
+    let (items, errors) = options().parse_tree_recover::<RecoveringItems, 2>("fn ;a();");
+    assert_eq!(items.unwrap().items.len(), 2);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].location.position, 3);
+}
+
+#[test]
+pub fn cut_backtrack_test() {
+    // lookahead commits to the item once `fn` is seen
+    let err = options().parse_tree::<Stmt, 2>("fn;").unwrap_err();
+    assert_eq!(err.location.position, 2);
+
+    // and so does backtracking, once it's parsed past the cut
+    for memoize in [false, true] {
+        let err = options()
+            .memoize(memoize)
+            .parse_tree::<BacktrackingStmt, 2>("fn;")
+            .unwrap_err();
+        assert_eq!(err.location.position, 2);
+        assert_eq!(
+            err.expected().map(|e| e.display_name()).collect::<Vec<_>>(),
+            ["Ident"]
+        );
+    }
+    let stmt = options()
+        .parse_tree::<BacktrackingStmt, 2>("fn a();")
+        .unwrap();
+    assert!(matches!(stmt, BacktrackingStmt::Item { .. }));
+
+    // without a cut, it fails like any other mismatch
+    let stmt = options()
+        .parse_tree::<BacktrackingUncutStmt, 2>("fn;")
+        .unwrap();
+    assert!(matches!(stmt, BacktrackingUncutStmt::Bare { .. }));
+}
@@ -0,0 +1,61 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use rs_typed_parser::{parse::ParseErrorKind, ParseOptions};
+
+rs_typed_parser::define_rule!(
+    pub enum Value {
+        List {
+            l_bracket: LBracket,
+            items: Vec<Value>,
+            r_bracket: RBracket,
+        },
+        Digits {
+            digits: Digits,
+        },
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "[")]
+    pub struct LBracket;
+    #[pattern(exact = "]")]
+    pub struct RBracket;
+    #[pattern(regex = r"[0-9]+")]
+    pub struct Digits;
+);
+
+#[test]
+pub fn fuel_test() {
+    let src = "[1 [2 3] [[4]] 5]".replace(' ', "");
+    assert!(ParseOptions::new()
+        .fuel(1000)
+        .parse_tree::<Value, 1>(&src)
+        .is_ok());
+
+    let err = ParseOptions::new()
+        .fuel(10)
+        .parse_tree::<Value, 1>(&src)
+        .unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::OutOfFuel);
+    assert_eq!(err.to_string(), "parse took too many steps");
+}
+
+#[test]
+pub fn cancel_test() {
+    let src = "[1[2[3[4]]]]";
+    let polls = Arc::new(AtomicUsize::new(0));
+    let options = ParseOptions::new().cancel_when({
+        let polls = polls.clone();
+        move || polls.fetch_add(1, Ordering::Relaxed) >= 5
+    });
+
+    let err = options.parse_tree::<Value, 1>(src).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::Cancelled);
+    assert_eq!(polls.load(Ordering::Relaxed), 6);
+
+    let options = ParseOptions::new().cancel_when(|| false);
+    assert!(options.parse_tree::<Value, 1>(src).is_ok());
+}
@@ -0,0 +1,101 @@
+use rs_typed_parser::{
+    ast::{Discard, WithSource},
+    parse_tree,
+};
+
+rs_typed_parser::define_rule!(
+    pub enum Stmt {
+        If { kw: If, cond: Ident },
+        Let { kw: Let, name: Ident },
+        Expr { ident: Ident },
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(keyword = "if")]
+    pub struct If;
+    #[pattern(keyword = "let", ident_char = "[a-z$]")]
+    pub struct Let;
+    #[pattern(regex = r"\s*[a-z0-9_$]+")]
+    #[reserved(If, Let)]
+    pub struct Ident;
+);
+
+fn parse(src: &str) -> Option<String> {
+    let ast = parse_tree::<Stmt, 1>(src).ok()?;
+    Some(format!("{:?}", WithSource { src, ast }))
+}
+
+#[test]
+pub fn keyword_boundary_test() {
+    assert_eq!(
+        parse("if x").as_deref(),
+        Some(r#"Stmt::If -> {If("if"), <Ident " x">}"#)
+    );
+    assert_eq!(
+        parse("iffy").as_deref(),
+        Some(r#"Stmt::Expr -> <Ident "iffy">"#)
+    );
+    assert_eq!(
+        parse("if_x").as_deref(),
+        Some(r#"Stmt::Expr -> <Ident "if_x">"#)
+    );
+    // `_` and digits can't continue a `let` keyword
+    assert_eq!(
+        parse("let_x").as_deref(),
+        Some(r#"Stmt::Let -> {Let("let"), <Ident "_x">}"#)
+    );
+    assert_eq!(
+        parse("let$x").as_deref(),
+        Some(r#"Stmt::Expr -> <Ident "let$x">"#)
+    );
+}
+
+#[test]
+pub fn reserved_keyword_test() {
+    assert_eq!(parse("if"), None);
+    assert_eq!(parse("let"), None);
+    assert!(parse("ifx").is_some());
+}
+
+rs_typed_parser::define_rule!(
+    pub struct Select {
+        select: Discard<SelectKw>,
+        #[transform(ignore_before<Space>)]
+        column: Ident,
+        #[transform(ignore_before<Space>)]
+        from: Discard<From>,
+        #[transform(ignore_before<Space>)]
+        table: Ident,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(keyword = "select", case_insensitive)]
+    pub struct SelectKw;
+    #[pattern(exact = "from", case_insensitive)]
+    pub struct From;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+#[test]
+pub fn case_insensitive_test() {
+    for src in ["select a from b", "SELECT a FROM b", "Select a From b"] {
+        let ast = parse_tree::<Select, 1>(src).unwrap();
+        assert_eq!(
+            format!("{}", WithSource { src, ast }),
+            r#"Select -> {<Ident "a">, <Ident "b">}"#
+        );
+    }
+
+    assert!(parse_tree::<Select, 1>("selectx a from b").is_err());
+
+    let src = "FROM";
+    let ast = parse_tree::<From, 1>(src).unwrap();
+    assert_eq!(format!("{}", WithSource { src, ast }), r#""from""#);
+
+    // the expected keyword is shown as it's defined, rather than by its name
+    let err = parse_tree::<Select, 1>("SELECT a b").unwrap_err();
+    assert_eq!(err.to_string(), "expected 'from', found `b`");
+}
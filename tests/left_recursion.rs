@@ -0,0 +1,68 @@
+use rs_typed_parser::{parse::ParseErrorKind, parse_tree, ParseOptions};
+
+rs_typed_parser::define_rule!(
+    pub enum Expr {
+        Add { lhs: Box<Expr>, op: Plus, rhs: Term },
+        Term { term: Term },
+    }
+    pub enum Term {
+        Mul {
+            lhs: Box<Product>,
+            op: Star,
+            rhs: Digits,
+        },
+        Digits {
+            digits: Digits,
+        },
+    }
+    pub struct Product {
+        term: Term,
+    }
+    pub struct Items {
+        items: Vec<Option<Digits>>,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "+")]
+    pub struct Plus;
+    #[pattern(exact = "*")]
+    pub struct Star;
+    #[pattern(regex = r"[0-9]+")]
+    pub struct Digits;
+);
+
+fn cycle(kind: ParseErrorKind) -> Vec<&'static str> {
+    match kind {
+        ParseErrorKind::LeftRecursion { cycle } => cycle,
+        kind => panic!("expected left recursion, got {kind:?}"),
+    }
+}
+
+#[test]
+pub fn direct_left_recursion_test() {
+    let err = parse_tree::<Expr, 2>("1+2").unwrap_err();
+    assert_eq!(cycle(err.kind), ["Expr", "Box", "Expr"]);
+
+    let err = ParseOptions::new()
+        .memoize(true)
+        .parse_tree::<Expr, 2>("1+2")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "left recursion in Expr -> Box -> Expr");
+}
+
+#[test]
+pub fn indirect_left_recursion_test() {
+    let err = parse_tree::<Term, 2>("1*2").unwrap_err();
+    assert_eq!(cycle(err.kind), ["Term", "Box", "Product", "Term"]);
+}
+
+#[test]
+pub fn empty_repetition_test() {
+    let err = parse_tree::<Items, 1>("12").unwrap_err();
+    assert!(matches!(err.kind, ParseErrorKind::EmptyRepetition));
+    assert_eq!(
+        err.to_string(),
+        "repeated item matched nothing, so it would repeat forever"
+    );
+}
@@ -0,0 +1,214 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rs_typed_parser::{
+    ast::{Backtrack, Token, WithSource},
+    lexer::{Conflict, Lexer},
+    parse::{Location, LocationRange},
+    parse_tree,
+    token::{TokenDef, TokenType},
+    ParseOptions,
+};
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "=")]
+    pub struct Assign;
+    #[pattern(exact = "==")]
+    pub struct Equals;
+    #[pattern(exact = "/")]
+    pub struct Slash;
+    #[pattern(exact = "if")]
+    pub struct If;
+    #[pattern(regex = r"[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+    #[pattern(regex = r"//[^\n]*")]
+    pub struct Comment;
+);
+
+fn lexer() -> Lexer {
+    Lexer::new([
+        TokenType::of::<Assign>(),
+        TokenType::of::<Equals>(),
+        TokenType::of::<Slash>(),
+        TokenType::of::<If>(),
+        TokenType::of::<Ident>(),
+    ])
+    .skip([TokenType::of::<Space>(), TokenType::of::<Comment>()])
+}
+
+fn lex(lexer: &Lexer, src: &str) -> Vec<String> {
+    lexer
+        .tokens(src)
+        .map(|token| {
+            let token = token.unwrap();
+            format!("{} {:?}", token.token_type.name(), token.range.slice(src))
+        })
+        .collect()
+}
+
+#[test]
+pub fn longest_match_test() {
+    let src = "if iffy == a / b // done\n= c";
+    assert_eq!(
+        lex(&lexer(), src),
+        [
+            r#"'if' "if""#,
+            r#"Ident "iffy""#,
+            r#"'==' "==""#,
+            r#"Ident "a""#,
+            r#"'/' "/""#,
+            r#"Ident "b""#,
+            r#"'=' "=""#,
+            r#"Ident "c""#,
+        ]
+    );
+}
+
+#[test]
+pub fn first_match_test() {
+    let lexer = lexer().conflict(Conflict::FirstMatch);
+    assert_eq!(
+        lex(&lexer, "iffy=="),
+        [r#"'if' "if""#, r#"Ident "fy""#, r#"'=' "=""#, r#"'=' "=""#]
+    );
+}
+
+#[test]
+pub fn lex_error_test() {
+    let src = "a = 1";
+    let lexer = lexer();
+    let mut tokens = lexer.tokens(src);
+    assert!(tokens.next().unwrap().is_ok());
+    assert!(tokens.next().unwrap().is_ok());
+
+    let err = tokens.next().unwrap().unwrap_err();
+    assert_eq!(err.location.position, 4);
+    assert_eq!(err.actual, "1");
+    assert_eq!(
+        err.to_string(),
+        "expected one of Assign, Equals, Slash, If, Ident, found `1`"
+    );
+    assert!(tokens.next().is_none());
+}
+
+static COUNTED_LEXES: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts how often it's lexed individually.
+pub struct Counted;
+
+impl TokenDef for Counted {
+    fn try_lex(src: &str, location: Location) -> Option<LocationRange> {
+        COUNTED_LEXES.fetch_add(1, Ordering::Relaxed);
+        rs_typed_parser::parse::lex_exact("x", src, location)
+    }
+
+    fn pattern() -> Option<String> {
+        Some("x".into())
+    }
+}
+
+#[test]
+pub fn pattern_matches_test() {
+    let lexer = Lexer::new([TokenType::of::<Counted>(), TokenType::of::<Ident>()]);
+
+    let matched: Vec<_> = lexer
+        .matches_at("abc", Location::default())
+        .map(|(token, _)| token.token_type.name())
+        .collect();
+    assert_eq!(matched, ["Ident"]);
+    assert_eq!(COUNTED_LEXES.load(Ordering::Relaxed), 0);
+
+    let matched: Vec<_> = lexer
+        .matches_at("xyz", Location::default())
+        .map(|(token, _)| (token.token_type.name(), token.range.slice("xyz")))
+        .collect();
+    assert_eq!(matched, [("Counted", "x"), ("Ident", "xyz")]);
+    assert_eq!(COUNTED_LEXES.load(Ordering::Relaxed), 1);
+
+    // a parse only lexes the tokens it asks for, even when the patterns of others match
+    let options = ParseOptions::new().lexer(&lexer);
+    assert!(options.parse_tree::<Ident, 1>("xyz").is_ok());
+    assert_eq!(COUNTED_LEXES.load(Ordering::Relaxed), 1);
+
+    // a token whose pattern matches less than the longest token doesn't need lexing
+    let (token, _) = lexer.lex_at("xyz", Location::default()).unwrap();
+    assert_eq!(token.token_type.name(), "Ident");
+    assert_eq!(COUNTED_LEXES.load(Ordering::Relaxed), 1);
+    let (token, _) = lexer.lex_at("x", Location::default()).unwrap();
+    assert_eq!(token.token_type.name(), "Counted");
+    assert_eq!(COUNTED_LEXES.load(Ordering::Relaxed), 2);
+}
+
+static RELEXED: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts how often it's lexed during a parse.
+pub struct Relexed;
+
+impl TokenDef for Relexed {
+    fn try_lex(src: &str, location: Location) -> Option<LocationRange> {
+        RELEXED.fetch_add(1, Ordering::Relaxed);
+        rs_typed_parser::parse::lex_exact("x", src, location)
+    }
+}
+
+rs_typed_parser::define_rule!(
+    pub enum Relexing {
+        Assign {
+            stmt: Backtrack<(Token<Relexed>, Assign)>,
+        },
+        Slash {
+            x: Token<Relexed>,
+            op: Slash,
+        },
+    }
+);
+
+#[test]
+pub fn lex_once_test() {
+    assert!(parse_tree::<Relexing, 1>("x/").is_ok());
+    assert_eq!(RELEXED.load(Ordering::Relaxed), 1);
+}
+
+rs_typed_parser::define_rule!(
+    pub enum Stmt {
+        Assign {
+            name: Ident,
+            op: Assign,
+            value: Ident,
+        },
+        Compare {
+            lhs: Ident,
+            op: Equals,
+            rhs: Ident,
+        },
+        If {
+            kw: If,
+            cond: Box<Stmt>,
+        },
+    }
+);
+
+#[test]
+pub fn parse_with_lexer_test() {
+    let lexer = lexer();
+    let options = ParseOptions::new().lexer(&lexer);
+    for src in ["a==b", "a=b", "ifa=b"] {
+        let with_lexer = options.parse_tree::<Stmt, 3>(src).unwrap();
+        let without = parse_tree::<Stmt, 3>(src).unwrap();
+        assert_eq!(
+            format!(
+                "{:?}",
+                WithSource {
+                    src,
+                    ast: with_lexer
+                }
+            ),
+            format!("{:?}", WithSource { src, ast: without }),
+        );
+    }
+
+    let err = options.parse_tree::<Stmt, 3>("a=").unwrap_err();
+    let plain_err = parse_tree::<Stmt, 3>("a=").unwrap_err();
+    assert_eq!(err.to_string(), plain_err.to_string());
+}
@@ -0,0 +1,154 @@
+use rs_typed_parser::{
+    ast::{Discard, Ignore, PopMode, PushMode, WithSource},
+    lexer::LexerMode,
+    token::TokenType,
+    ParseOptions, Rule,
+};
+
+rs_typed_parser::define_rule!(
+    pub struct Exprs {
+        exprs: Vec<Expr>,
+    }
+    pub enum Expr {
+        Ident { ident: Ident },
+        Str { value: Box<Str> },
+    }
+    pub struct Str {
+        open: Discard<Quote>,
+        parts: PushMode<StrMode, Vec<Part>>,
+        close: Discard<Quote>,
+    }
+    pub enum Quoted {
+        Terminated {
+            open: Quote,
+            text: StrText,
+            semi: Semi,
+        },
+        Str {
+            open: Quote,
+            text: PushMode<StrMode, StrText>,
+            close: Quote,
+        },
+    }
+    pub enum Part {
+        Ident {
+            ident: Ident,
+        },
+        Text {
+            text: StrText,
+        },
+        Interp {
+            open: Discard<DollarBrace>,
+            expr: PopMode<Expr>,
+            close: Discard<RBrace>,
+        },
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "\"")]
+    pub struct Quote;
+    #[pattern(exact = "${")]
+    pub struct DollarBrace;
+    #[pattern(exact = "}")]
+    pub struct RBrace;
+    #[pattern(regex = "[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = r#"[^"$]+"#)]
+    pub struct StrText;
+    #[pattern(exact = ";")]
+    pub struct Semi;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+pub struct StrMode;
+
+impl LexerMode for StrMode {
+    fn allows(token_type: &'static TokenType) -> bool {
+        [
+            TokenType::of::<Quote>(),
+            TokenType::of::<DollarBrace>(),
+            TokenType::of::<RBrace>(),
+            TokenType::of::<StrText>(),
+        ]
+        .contains(&token_type)
+    }
+}
+
+fn parse(src: &str, look_ahead: usize) -> Option<String> {
+    parse_as::<Expr>(src, look_ahead)
+}
+
+fn parse_as<T: Rule>(src: &str, look_ahead: usize) -> Option<String> {
+    let options = ParseOptions::new().look_ahead(look_ahead);
+    let parse = |options: ParseOptions| {
+        let ast = options.parse_tree_dyn::<T>(src).ok()?;
+        Some(format!("{:?}", WithSource { src, ast }))
+    };
+
+    // the same rule is parsed in different modes, which memoization has to tell apart
+    let out = parse(options.clone());
+    assert_eq!(parse(options.memoize(true)), out);
+    out
+}
+
+#[test]
+pub fn lexer_mode_test() {
+    for look_ahead in 1..=3 {
+        assert_eq!(
+            parse("ab", look_ahead).as_deref(),
+            Some(r#"Expr::Ident -> <Ident "ab">"#)
+        );
+
+        let str = parse(r#""ab""#, look_ahead).unwrap();
+        assert!(str.contains(r#"<StrText "ab">"#), "{str}");
+        assert!(!str.contains("Ident"), "{str}");
+    }
+}
+
+#[test]
+pub fn pop_mode_test() {
+    for look_ahead in 1..=3 {
+        let str = parse(r#""a ${b} c""#, look_ahead).unwrap();
+        assert!(str.contains(r#"<StrText "a ">"#), "{str}");
+        assert!(str.contains(r#"Expr::Ident -> <Ident "b">"#), "{str}");
+        assert!(str.contains(r#"<StrText " c">"#), "{str}");
+
+        let nested = parse(r#""a${"b${c}"}""#, look_ahead).unwrap();
+        assert_eq!(nested.matches("StrText").count(), 2, "{nested}");
+        assert_eq!(nested.matches("<Ident").count(), 1, "{nested}");
+
+        // text can't be lexed within the interpolation
+        assert_eq!(parse(r#""${a b}""#, look_ahead), None);
+        assert_eq!(parse(r#""${"a"}"#, look_ahead), None);
+    }
+}
+
+#[test]
+pub fn mode_end_test() {
+    // looking ahead past the end of a string goes back to the outer mode
+    for look_ahead in 1..=3 {
+        let exprs = parse_as::<Exprs>(r#""${a}"b"c"d"#, look_ahead).unwrap();
+        assert_eq!(exprs.matches("<Ident").count(), 3, "{exprs}");
+
+        let exprs = parse_as::<Exprs>(r#"""b"#, look_ahead).unwrap();
+        assert_eq!(exprs.matches("<Ident").count(), 1, "{exprs}");
+    }
+}
+
+#[test]
+pub fn mode_look_ahead_test() {
+    // the first alternative looks ahead at the text with the space skipped as trivia, before the
+    // second switches to a mode that doesn't skip it
+    let src = r#"" ab""#;
+    for look_ahead in 3..=4 {
+        let ast = ParseOptions::new()
+            .look_ahead(look_ahead)
+            .trivia::<Ignore<Space>>()
+            .parse_tree_dyn::<Quoted>(src)
+            .unwrap();
+        let out = format!("{:?}", WithSource { src, ast });
+        assert!(out.contains(r#"<StrText " ab">"#), "{out}");
+    }
+}
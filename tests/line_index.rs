@@ -0,0 +1,46 @@
+use rs_typed_parser::{
+    parse::{Location, LocationRange},
+    LineColumn, LineIndex,
+};
+
+#[test]
+pub fn line_column_test() {
+    let src = "ab\r\ncé\n\u{1F600}x";
+    let index = LineIndex::new(src);
+
+    assert_eq!(index.line_count(), 3);
+    assert_eq!(index.line(1), Some("ab"));
+    assert_eq!(index.line(2), Some("cé"));
+    assert_eq!(index.line(3), Some("\u{1F600}x"));
+    assert_eq!(index.line(4), None);
+
+    let at = |position| index.line_column(Location { position });
+
+    assert_eq!(
+        at(0),
+        LineColumn {
+            line: 1,
+            column: 1,
+            column_utf16: 1
+        },
+    );
+    assert_eq!(at(4).to_string(), "2:1");
+
+    let emoji_end = src.find('x').unwrap();
+    assert_eq!(
+        at(emoji_end),
+        LineColumn {
+            line: 3,
+            column: 5,
+            column_utf16: 3
+        },
+    );
+
+    let range = index.line_column_range(LocationRange {
+        start: Location { position: 4 },
+        end: Location {
+            position: src.len(),
+        },
+    });
+    assert_eq!((range.start.line, range.end.line), (2, 3));
+}
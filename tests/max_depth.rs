@@ -0,0 +1,64 @@
+use rs_typed_parser::{parse::ParseErrorKind, parse_tree, ParseOptions};
+
+rs_typed_parser::define_rule!(
+    pub enum Value {
+        List {
+            l_bracket: LBracket,
+            items: Vec<Value>,
+            r_bracket: RBracket,
+        },
+        Digits {
+            digits: Digits,
+        },
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "[")]
+    pub struct LBracket;
+    #[pattern(exact = "]")]
+    pub struct RBracket;
+    #[pattern(regex = r"[0-9]+")]
+    pub struct Digits;
+);
+
+fn nested(depth: usize) -> String {
+    "[".repeat(depth) + "1" + &"]".repeat(depth)
+}
+
+#[test]
+pub fn max_depth_test() {
+    let src = nested(100_000);
+    let err = parse_tree::<Value, 1>(&src).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::NestingTooDeep { max_depth: 128 });
+    assert_eq!(
+        err.to_string(),
+        "nesting too deep, exceeding the limit of 128"
+    );
+
+    let options = ParseOptions::new().max_depth(8);
+    let src = nested(10);
+    let err = options.parse_tree::<Value, 1>(&src).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::NestingTooDeep { max_depth: 8 });
+    assert!(options.parse_tree::<Value, 1>(&nested(2)).is_ok());
+}
+
+#[test]
+pub fn max_depth_limit_test() {
+    assert!(parse_tree::<Value, 1>(&nested(128)).is_ok());
+    let src = nested(129);
+    let err = parse_tree::<Value, 1>(&src).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::NestingTooDeep { max_depth: 128 });
+
+    let options = ParseOptions::new().max_depth(8);
+    assert!(options.parse_tree::<Value, 1>(&nested(8)).is_ok());
+    let src = nested(9);
+    let err = options.parse_tree::<Value, 1>(&src).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::NestingTooDeep { max_depth: 8 });
+}
+
+#[test]
+pub fn max_depth_flat_test() {
+    let src = format!("[{}]", "[1]".repeat(10_000));
+    assert!(parse_tree::<Value, 1>(&src).is_ok());
+}
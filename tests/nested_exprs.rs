@@ -1,5 +1,8 @@
-use rs_typed_parser::ast::{
-    CompoundToken, DelimitedList, Discard, DualParse, Ignore, InfixChain, WithSource,
+use std::cell::Cell;
+
+use rs_typed_parser::{
+    ast::{CompoundToken, DelimitedList, Discard, DualParse, Ignore, InfixChain, WithSource},
+    trace::{TraceEvent, Tracer},
 };
 
 type Blank = Ignore<Space>;
@@ -15,6 +18,7 @@ rs_typed_parser::define_rule!(
     pub struct Expr {
         value: InfixChain<BaseExpr, InfixOp>,
     }
+    #[label("expression")]
     pub enum BaseExpr {
         Ident {
             ident: Ident,
@@ -126,3 +130,114 @@ pub fn parse_test3() {
     let ast = rs_typed_parser::parse_tree::<Expr, 1>(src).unwrap();
     println!("{:#}", WithSource { src, ast });
 }
+
+#[test]
+pub fn error_line_column_test() {
+    let src = "{\n  a,\n  b +\n}";
+    let err = rs_typed_parser::parse_tree::<Braces, 2>(src).unwrap_err();
+    assert_eq!(err.line_column().to_string(), "4:1");
+    assert_eq!(err.actual, "}");
+}
+
+#[test]
+pub fn error_display_test() {
+    let src = "{\n  a,\n  b + +c\n}";
+    // the label is reported where the expression starts, once trivia is skipped
+    let err = rs_typed_parser::ParseOptions::new()
+        .trivia::<Blank>()
+        .parse_tree::<Braces, 2>(src)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "expected expression, found `+`");
+    assert_eq!(
+        err.display().to_string(),
+        "error: expected expression, found `+`\n --> 3:7\n  |\n3 |   b + +c\n  |       ^"
+    );
+    // without its source, only the message can be shown
+    let detached = rs_typed_parser::ParseError {
+        src: None,
+        ..err.clone()
+    };
+    assert_eq!(
+        detached.display().to_string(),
+        "error: expected expression, found `+`"
+    );
+    assert!(err
+        .display()
+        .color(true)
+        .to_string()
+        .contains("\x1b[1;31m^"));
+}
+
+/// Counts how many times any rule is parsed, excluding pre-parses.
+#[derive(Default)]
+struct ParseCounter(Cell<usize>);
+
+impl Tracer for ParseCounter {
+    fn event(&self, event: TraceEvent) {
+        if let TraceEvent::Enter {
+            pre_parse: false, ..
+        } = event
+        {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+}
+
+fn count_parses(options: rs_typed_parser::ParseOptions, src: &str) -> usize {
+    let counter = ParseCounter::default();
+    options
+        .tracer(&counter)
+        .parse_tree::<Braces, 2>(src)
+        .unwrap();
+    counter.0.get()
+}
+
+#[test]
+pub fn memoize_test() {
+    let src = "{a, {[b] + {{{c}, [1]}}} - {d + {e}}, f}";
+    let options = rs_typed_parser::ParseOptions::new().memoize(true);
+    let memoized = options.parse_tree::<Braces, 2>(src).unwrap();
+    let plain = rs_typed_parser::parse_tree::<Braces, 2>(src).unwrap();
+
+    assert_eq!(
+        format!("{:?}", WithSource { src, ast: memoized }),
+        format!("{:?}", WithSource { src, ast: plain }),
+    );
+
+    // speculative parses are reused rather than redone
+    let memoized_parses = count_parses(options.clone(), src);
+    let plain_parses = count_parses(rs_typed_parser::ParseOptions::new(), src);
+    assert!(
+        memoized_parses < plain_parses,
+        "{memoized_parses} >= {plain_parses}"
+    );
+
+    let err = options.parse_tree::<Braces, 2>("{a, {b +}}").unwrap_err();
+    let plain_err = rs_typed_parser::parse_tree::<Braces, 2>("{a, {b +}}").unwrap_err();
+    assert_eq!(err.to_string(), plain_err.to_string());
+}
+
+#[test]
+pub fn dyn_look_ahead_test() {
+    let src = "{a, {[b] + {{{c}, [1]}}} - {d + {e}}, f}";
+    let dynamic = rs_typed_parser::parse_tree_dyn::<Braces>(src, 2).unwrap();
+    let sized = rs_typed_parser::parse_tree::<Braces, 2>(src).unwrap();
+
+    assert_eq!(
+        format!("{:?}", WithSource { src, ast: dynamic }),
+        format!("{:?}", WithSource { src, ast: sized }),
+    );
+
+    let options = rs_typed_parser::ParseOptions::new()
+        .look_ahead(2)
+        .memoize(true);
+    let err = options.parse_tree_dyn::<Braces>("{a, {b +}}").unwrap_err();
+    let sized_err = rs_typed_parser::parse_tree::<Braces, 2>("{a, {b +}}").unwrap_err();
+    assert_eq!(err.to_string(), sized_err.to_string());
+
+    // without any lookahead, every rule is committed to before checking it
+    let dynamic = rs_typed_parser::parse_tree_dyn::<Braces>(src, 0).unwrap_err();
+    let sized = rs_typed_parser::parse_tree::<Braces, 0>(src).unwrap_err();
+    assert_eq!(dynamic.to_string(), sized.to_string());
+    assert_eq!(dynamic.location.position, 0);
+}
@@ -0,0 +1,131 @@
+use rs_typed_parser::{
+    ast::{Associativity, InfixOperator, PostfixOperator, Precedence, PrefixOperator, WithSource},
+    parse_tree,
+};
+
+rs_typed_parser::define_rule!(
+    pub struct Expr {
+        value: Precedence<Digits, BinaryOp, Minus, Bang>,
+    }
+    pub enum BinaryOp {
+        Add { op: Plus },
+        Mul { op: Star },
+        Pow { op: Caret },
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "+")]
+    pub struct Plus;
+    #[pattern(exact = "*")]
+    pub struct Star;
+    #[pattern(exact = "^")]
+    pub struct Caret;
+    #[pattern(exact = "-")]
+    pub struct Minus;
+    #[pattern(exact = "!")]
+    pub struct Bang;
+    #[pattern(regex = r"[0-9]+")]
+    pub struct Digits;
+);
+
+impl InfixOperator for BinaryOp {
+    fn precedence(&self) -> u32 {
+        match self {
+            BinaryOp::Add { .. } => 1,
+            BinaryOp::Mul { .. } => 2,
+            BinaryOp::Pow { .. } => 4,
+        }
+    }
+
+    fn associativity(&self) -> Associativity {
+        match self {
+            BinaryOp::Pow { .. } => Associativity::Right,
+            _ => Associativity::Left,
+        }
+    }
+}
+
+impl PrefixOperator for Minus {
+    fn precedence(&self) -> u32 {
+        3
+    }
+}
+
+impl PostfixOperator for Bang {
+    fn precedence(&self) -> u32 {
+        5
+    }
+}
+
+/// Renders the tree with explicit parentheses.
+fn group(src: &str, expr: &Precedence<Digits, BinaryOp, Minus, Bang>) -> String {
+    let text = |range: rs_typed_parser::parse::LocationRange| range.slice(src).to_string();
+    match expr {
+        Precedence::Atom(digits) => text(digits.range),
+        Precedence::Prefix { operand, .. } => format!("(-{})", group(src, operand)),
+        Precedence::Postfix { operand, .. } => format!("({}!)", group(src, operand)),
+        Precedence::Binary { lhs, op, rhs } => {
+            let op = match op {
+                BinaryOp::Add { .. } => "+",
+                BinaryOp::Mul { .. } => "*",
+                BinaryOp::Pow { .. } => "^",
+            };
+            format!("({} {op} {})", group(src, lhs), group(src, rhs))
+        }
+    }
+}
+
+#[test]
+pub fn precedence_test() {
+    for (src, expected) in [
+        ("1", "1"),
+        ("1+2*3+4", "((1 + (2 * 3)) + 4)"),
+        ("2^3^4*5", "((2 ^ (3 ^ 4)) * 5)"),
+        ("-1*2", "((-1) * 2)"),
+        ("-2^3", "(-(2 ^ 3))"),
+        ("-3!+--4", "((-(3!)) + (-(-4)))"),
+        ("1*2!!^3", "(1 * (((2!)!) ^ 3))"),
+    ] {
+        let ast = parse_tree::<Expr, 1>(src).unwrap();
+        assert_eq!(group(src, &ast.value), expected, "{src}");
+    }
+}
+
+#[test]
+pub fn precedence_print_test() {
+    let src = "1+2*3";
+    let ast = parse_tree::<Expr, 1>(src).unwrap();
+    assert_eq!(
+        WithSource { src, ast }.to_string(),
+        r#"Expr -> Binary(<Digits "1">, "+", Binary(<Digits "2">, "*", <Digits "3">))"#,
+    );
+
+    assert!(parse_tree::<Expr, 1>("1+").is_err());
+}
+
+#[test]
+pub fn deep_precedence_test() {
+    // long chains of operators nest the tree deeply, without nesting the parse
+    let depth = 10_000;
+    let src = format!("{}1{}", "-".repeat(depth), "^1".repeat(depth));
+    let ast = parse_tree::<Expr, 1>(&src).unwrap();
+
+    let mut expr = &ast.value;
+    let (mut prefixes, mut powers) = (0, 0);
+    loop {
+        match expr {
+            Precedence::Prefix { operand, .. } => {
+                prefixes += 1;
+                expr = operand;
+            }
+            Precedence::Binary { lhs, rhs, .. } => {
+                assert!(matches!(**lhs, Precedence::Atom(_)));
+                powers += 1;
+                expr = rhs;
+            }
+            _ => break,
+        }
+    }
+    assert_eq!((prefixes, powers), (depth, depth));
+}
@@ -0,0 +1,52 @@
+use rs_typed_parser::{
+    ast::{DelimitedList, Discard, Ignore, Recognize, WithSource},
+    ParseOptions,
+};
+
+rs_typed_parser::define_rule!(
+    pub struct Import {
+        kw: Discard<Use>,
+        path: Recognize<Path>,
+        semi: Discard<Semi>,
+    }
+    pub struct Path {
+        parts: DelimitedList<Ident, Dot, false>,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(keyword = "use")]
+    pub struct Use;
+    #[pattern(exact = ".")]
+    pub struct Dot;
+    #[pattern(exact = ";")]
+    pub struct Semi;
+    #[pattern(regex = "[a-z]+")]
+    #[reserved(Use)]
+    pub struct Ident;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+fn parse(src: &str) -> Option<Import> {
+    ParseOptions::new()
+        .trivia::<Ignore<Space>>()
+        .parse_tree::<Import, 1>(src)
+        .ok()
+}
+
+#[test]
+pub fn recognize_test() {
+    let src = "use foo.bar . baz ;";
+    let import = parse(src).unwrap();
+    assert_eq!(import.path.text(src), "foo.bar . baz");
+    assert_eq!(
+        format!("{:?}", WithSource { src, ast: import }),
+        r#"Import -> {Discard<Use>, "foo.bar . baz", Discard<Semi>}"#
+    );
+
+    let src = "use\n  x\n;";
+    assert_eq!(parse(src).unwrap().path.text(src), "x");
+
+    assert!(parse("use foo.;").is_none());
+}
@@ -0,0 +1,137 @@
+use rs_typed_parser::{
+    ast::{DelimitedList, Discard, Ignore, Recover, Reject},
+    parse_tree_recover, Either,
+};
+
+type Blank = Ignore<Space>;
+
+rs_typed_parser::define_rule!(
+    pub struct Block {
+        #[transform(ignore_before<Space>)]
+        l_brace: Discard<LBrace>,
+        stmts: Vec<Recover<Stmt, Semi, (Blank, RBrace)>>,
+        #[transform(ignore_before<Space>)]
+        r_brace: Discard<RBrace>,
+    }
+    pub struct Stmt {
+        #[transform(ignore_before<Space>)]
+        name: Ident,
+        #[transform(ignore_before<Space>)]
+        eq: Discard<Eq>,
+        #[transform(ignore_before<Space>)]
+        value: Ident,
+        #[transform(ignore_before<Space>)]
+        semi: Discard<Semi>,
+    }
+    pub struct List {
+        l_bracket: Discard<LBracket>,
+        items: DelimitedList<Recover<Ident, Reject, Either<Comma, RBracket>>, Comma>,
+        r_bracket: Discard<RBracket>,
+    }
+    pub struct Items {
+        l_brace: Discard<LBrace>,
+        items: Vec<Recover<Item, Semi, (Blank, RBrace)>>,
+        #[transform(ignore_before<Space>)]
+        r_brace: Discard<RBrace>,
+    }
+    pub enum Item {
+        Assign {
+            #[transform(ignore_before<Space>)]
+            name: Ident,
+            #[transform(ignore_before<Space>)]
+            eq: Discard<Eq>,
+            #[transform(ignore_before<Space>)]
+            value: Ident,
+            #[transform(ignore_before<Space>)]
+            semi: Discard<Semi>,
+        },
+        List {
+            #[transform(ignore_before<Space>)]
+            list: List,
+            #[transform(ignore_before<Space>)]
+            semi: Discard<Semi>,
+        },
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "{")]
+    pub struct LBrace;
+    #[pattern(exact = "}")]
+    pub struct RBrace;
+    #[pattern(exact = "[")]
+    pub struct LBracket;
+    #[pattern(exact = "]")]
+    pub struct RBracket;
+    #[pattern(exact = ";")]
+    pub struct Semi;
+    #[pattern(exact = ",")]
+    pub struct Comma;
+    #[pattern(exact = "=")]
+    pub struct Eq;
+    #[pattern(regex = r"[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+#[test]
+pub fn recover_statements_test() {
+    let src = "{ a = b; c = ; d = e; f g h; }";
+    let (ast, diagnostics) = parse_tree_recover::<Block, 2>(src);
+    let ast = ast.unwrap();
+
+    let ok = ast.stmts.iter().filter(|s| s.ok().is_some()).count();
+    assert_eq!((ast.stmts.len(), ok), (4, 2));
+    assert_eq!(
+        diagnostics
+            .iter()
+            .map(|d| d.line_column().column)
+            .collect::<Vec<_>>(),
+        [14, 25],
+    );
+
+    assert!(rs_typed_parser::parse_tree::<Block, 2>(src).is_err());
+}
+
+#[test]
+pub fn recover_delimited_test() {
+    let src = "[a,b c,d,e f]";
+    let (ast, diagnostics) = parse_tree_recover::<List, 1>(src);
+    let items = ast.unwrap().items.items;
+
+    assert_eq!(items.len(), 4);
+    assert!(items[1].ok().is_none());
+    assert!(items[3].ok().is_none());
+    assert_eq!(
+        diagnostics
+            .iter()
+            .map(|d| d.location.position)
+            .collect::<Vec<_>>(),
+        [4, 10],
+    );
+
+    // an empty item can't be skipped, so it's reported without recovery
+    let (ast, diagnostics) = parse_tree_recover::<List, 1>("[a,,b]");
+    assert!(ast.is_none());
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+pub fn recover_enum_test() {
+    let src = "{ a = b; [x y,z]; c = ; [d]; e; }";
+    let (ast, diagnostics) = parse_tree_recover::<Items, 2>(src);
+    let items = ast.unwrap().items;
+
+    // the error inside the list is recovered by the list, the others by the item
+    let ok: Vec<_> = items.iter().map(|item| item.ok().is_some()).collect();
+    assert_eq!(ok, [true, true, false, true, false]);
+    assert!(matches!(items[1].ok(), Some(Item::List { .. })));
+    assert_eq!(
+        diagnostics
+            .iter()
+            .map(|d| d.location.position)
+            .collect::<Vec<_>>(),
+        [11, 22, 30],
+    );
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::number;
+use rs_typed_parser::{
+    ast::{Discard, Ignore, NonEmpty, Repeat},
+    ParseError, ParseOptions, Rule,
+};
+
+rs_typed_parser::define_rule!(
+    pub struct Pair {
+        digits: Repeat<Digit, 2, 3>,
+        semi: Semi,
+    }
+    pub struct Args {
+        l_paren: Discard<LParen>,
+        args: NonEmpty<Ident, Comma>,
+        r_paren: Discard<RParen>,
+    }
+    pub struct TrailingArgs {
+        l_paren: Discard<LParen>,
+        args: Repeat<Ident, 1, 2, Comma, true>,
+        r_paren: Discard<RParen>,
+    }
+    pub struct Triple {
+        digits: [Digit; 3],
+        skipped: Discard<[Digit; 2]>,
+    }
+    pub enum Single {
+        One {
+            digit: Repeat<Digit, 1, 1>,
+            semi: Semi,
+        },
+        Many {
+            digits: Vec<Digit>,
+            semi: Semi,
+        },
+    }
+    pub enum Double {
+        Two {
+            digits: Repeat<Digit, 2, 2>,
+            semi: Semi,
+        },
+        One {
+            digit: Digit,
+            semi: Semi,
+        },
+    }
+    pub struct Numbers {
+        #[transform(for_each<with_source<number<Digit>>>)]
+        numbers: Repeat<u32, 1>,
+        semi: Semi,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "(")]
+    pub struct LParen;
+    #[pattern(exact = ")")]
+    pub struct RParen;
+    #[pattern(exact = ",")]
+    pub struct Comma;
+    #[pattern(exact = ";")]
+    pub struct Semi;
+    #[pattern(regex = "[0-9]")]
+    pub struct Digit;
+    #[pattern(regex = "[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+fn parse<T: Rule>(src: &str) -> Result<T, ParseError<'_>> {
+    ParseOptions::new()
+        .trivia::<Ignore<Space>>()
+        .parse_tree::<T, 1>(src)
+}
+
+fn error_at<T: Rule>(src: &str) -> (usize, Vec<&'static str>) {
+    let err = parse::<T>(src).map(|_| ()).unwrap_err();
+    let expected = err.expected().map(|e| e.display_name()).collect();
+    (err.location.position, expected)
+}
+
+#[test]
+pub fn repeat_test() {
+    assert_eq!(parse::<Pair>("1 2;").unwrap().digits.items.len(), 2);
+    assert_eq!(parse::<Pair>("1 2 3;").unwrap().digits.items.len(), 3);
+
+    // too few items is reported where the next one was expected
+    assert_eq!(error_at::<Pair>("1 ;"), (2, vec!["Digit"]));
+    // and no more than the maximum are parsed
+    assert_eq!(error_at::<Pair>("1 2 3 4;"), (6, vec!["Semi"]));
+}
+
+#[test]
+pub fn repeat_look_ahead_test() {
+    let parse = |src| {
+        ParseOptions::new()
+            .trivia::<Ignore<Space>>()
+            .parse_tree::<(Single, Double), 2>(src)
+    };
+
+    // lookahead checks that there's no more than the maximum, and all of the minimum
+    let (single, double) = parse("1 2; 3;").unwrap();
+    assert!(matches!(single, Single::Many { .. }));
+    assert!(matches!(double, Double::One { .. }));
+
+    let (single, double) = parse("1; 2 3;").unwrap();
+    assert!(matches!(single, Single::One { .. }));
+    assert!(matches!(double, Double::Two { .. }));
+}
+
+#[test]
+pub fn non_empty_test() {
+    assert_eq!(parse::<Args>("(a, b)").unwrap().args.items.len(), 2);
+    assert_eq!(error_at::<Args>("()"), (1, vec!["Ident"]));
+    assert_eq!(error_at::<Args>("(a,)"), (3, vec!["Ident"]));
+
+    assert_eq!(parse::<TrailingArgs>("(a,)").unwrap().args.items.len(), 1);
+    assert_eq!(
+        parse::<TrailingArgs>("(a, b,)").unwrap().args.items.len(),
+        2
+    );
+    assert_eq!(error_at::<TrailingArgs>("(a, b, c)"), (7, vec!["RParen"]));
+}
+
+#[test]
+pub fn array_test() {
+    let src = "1 2 3 4 5";
+    let triple = parse::<Triple>(src).unwrap();
+    let digits = triple.digits.map(|digit| digit.range.slice(src));
+    assert_eq!(digits, ["1", "2", "3"]);
+
+    assert_eq!(error_at::<Triple>("1 2"), (3, vec!["Digit"]));
+}
+
+#[test]
+pub fn transform_test() {
+    let numbers = parse::<Numbers>("1 2 3;").unwrap().numbers.items;
+    assert_eq!(numbers, [1, 2, 3]);
+    assert_eq!(error_at::<Numbers>(";"), (0, vec!["Digit"]));
+}
@@ -0,0 +1,69 @@
+#![allow(non_camel_case_types)]
+
+mod common;
+
+use common::number;
+use rs_typed_parser::{
+    ast::{transform::TransformWithSource, Discard, Token, WithSource},
+    parse_tree,
+};
+
+pub struct ident_name;
+
+impl TransformWithSource<String> for ident_name {
+    type Input = Token<Ident>;
+
+    fn transform(input: Self::Input, src: &str) -> String {
+        input.range.slice(src).into()
+    }
+}
+
+pub struct unescape;
+
+impl TransformWithSource<String> for unescape {
+    type Input = Token<Str>;
+
+    fn transform(input: Self::Input, src: &str) -> String {
+        let quoted = input.range.slice(src);
+        quoted[1..quoted.len() - 1].replace("\\\"", "\"")
+    }
+}
+
+rs_typed_parser::define_rule!(
+    pub struct Entry {
+        #[transform(with_source<ident_name>)]
+        key: String,
+        eq: Discard<Eq>,
+        #[transform(ignore_before<Space>, with_source<unescape>)]
+        value: String,
+        #[transform(for_each<compose<ignore_before<Space>, with_source<number<Digits>>>>)]
+        numbers: Vec<u32>,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "=")]
+    pub struct Eq;
+    #[pattern(regex = "[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = r#""(?:[^"\\]|\\.)*""#)]
+    pub struct Str;
+    #[pattern(regex = "[0-9]+")]
+    pub struct Digits;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+#[test]
+pub fn source_transform_test() {
+    let src = r#"name= "a \"b\"" 1 22  333"#;
+    let entry = parse_tree::<Entry, 1>(src).unwrap();
+    assert_eq!(entry.key, "name");
+    assert_eq!(entry.value, r#"a "b""#);
+    assert_eq!(entry.numbers, [1, 22, 333]);
+    assert_eq!(
+        format!("{:?}", WithSource { src, ast: entry }),
+        r#"Entry -> {"name", Discard<Eq>, "a \"b\"", [1, 22, 333]}"#
+    );
+    assert!(parse_tree::<Entry, 1>("name=\"a\" x").is_err());
+}
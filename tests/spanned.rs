@@ -0,0 +1,84 @@
+use rs_typed_parser::{
+    ast::{Discard, Ignore, Spanned},
+    parse::LocationRange,
+    parse_tree, ParseOptions,
+};
+
+rs_typed_parser::define_rule!(
+    pub enum Expr {
+        Call {
+            name: Spanned<Ident>,
+            args: Spanned<Args>,
+        },
+        Var {
+            #[transform(ignore_before<Space>, spanned)]
+            name: Spanned<Ident>,
+        },
+    }
+    pub struct Args {
+        l_paren: Discard<LParen>,
+        #[transform(spanned<delimited<Comma, false>>)]
+        items: Spanned<Vec<Ident>>,
+        r_paren: Discard<RParen>,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "(")]
+    pub struct LParen;
+    #[pattern(exact = ")")]
+    pub struct RParen;
+    #[pattern(exact = ",")]
+    pub struct Comma;
+    #[pattern(regex = "[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+fn slice(src: &str, span: LocationRange) -> &str {
+    span.slice(src)
+}
+
+#[test]
+pub fn spanned_test() {
+    let src = "  f ( a , b ) ";
+    let expr = ParseOptions::new()
+        .trivia::<Ignore<Space>>()
+        .parse_tree::<Expr, 2>(src)
+        .unwrap();
+
+    let Expr::Call { name, args } = expr else {
+        panic!("expected a call");
+    };
+    assert_eq!(slice(src, name.span), "f");
+    // the discarded parens are part of the span, but not the trivia around them
+    assert_eq!(slice(src, args.span), "( a , b )");
+    assert_eq!(slice(src, args.value.items.span), "a , b");
+}
+
+#[test]
+pub fn spanned_transform_test() {
+    let src = "   x";
+    let Expr::Var { name } = parse_tree::<Expr, 2>(src).unwrap() else {
+        panic!("expected a variable");
+    };
+    assert_eq!(slice(src, name.span), "x");
+    assert_eq!(name.span.start.position, 3);
+}
+
+#[test]
+pub fn empty_span_test() {
+    let src = "f( )";
+    let expr = ParseOptions::new()
+        .trivia::<Ignore<Space>>()
+        .parse_tree::<Expr, 2>(src)
+        .unwrap();
+
+    let Expr::Call { args, .. } = expr else {
+        panic!("expected a call");
+    };
+    assert_eq!(slice(src, args.span), "( )");
+    let items = args.value.items.span;
+    assert_eq!((items.start.position, items.end.position), (3, 3));
+}
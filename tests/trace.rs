@@ -0,0 +1,76 @@
+use std::cell::RefCell;
+
+use rs_typed_parser::{
+    trace::{TraceEvent, TraceLog, Tracer},
+    ParseOptions,
+};
+
+rs_typed_parser::define_rule!(
+    pub enum Item {
+        Call {
+            ident: Ident,
+            l_paren: LParen,
+            r_paren: RParen,
+        },
+        Ident {
+            ident: Ident,
+        },
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "(")]
+    pub struct LParen;
+    #[pattern(exact = ")")]
+    pub struct RParen;
+    #[pattern(regex = r"[a-z]+")]
+    pub struct Ident;
+);
+
+#[derive(Default)]
+struct Events(RefCell<Vec<String>>);
+
+impl Tracer for Events {
+    fn event(&self, event: TraceEvent) {
+        let event = match event {
+            TraceEvent::Enter {
+                rule, pre_parse, ..
+            } => format!("enter {rule} {pre_parse}"),
+            TraceEvent::Exit { rule, result, .. } => format!("exit {rule} {}", result.is_ok()),
+            TraceEvent::Lex { token_type, .. } => format!("lex {}", token_type.name()),
+            _ => return,
+        };
+        self.0.borrow_mut().push(event);
+    }
+}
+
+#[test]
+pub fn tracer_test() {
+    let events = Events::default();
+    ParseOptions::new()
+        .tracer(&events)
+        .parse_tree::<Item, 2>("abc")
+        .unwrap();
+
+    let events = events.0.into_inner();
+    assert_eq!(events.first().map(String::as_str), Some("enter Item false"));
+    assert_eq!(events.last().map(String::as_str), Some("exit Item true"));
+    assert!(events.iter().any(|event| event == "lex '('"));
+}
+
+#[test]
+pub fn trace_log_test() {
+    let log = TraceLog::new();
+    ParseOptions::new()
+        .tracer(&log)
+        .parse_tree::<Item, 2>("f()")
+        .unwrap();
+
+    let log = log.into_string();
+    println!("{log}");
+    let lines: Vec<_> = log.lines().collect();
+    assert_eq!(lines.first(), Some(&"parse Item @0"));
+    assert!(lines.contains(&"  check Ident >> LParen >> RParen @0: ok"));
+    assert!(lines.contains(&"      look-ahead[1] = LParen 1..2"));
+    assert!(lines.contains(&"ok Item to @3"));
+}
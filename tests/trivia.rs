@@ -0,0 +1,104 @@
+use rs_typed_parser::{
+    ast::{DelimitedList, Discard, Ignore, NoTrivia, WithSource},
+    parse::ParseErrorKind,
+    Either, ParseOptions,
+};
+
+type Trivia = Ignore<Either<Space, Comment>>;
+
+rs_typed_parser::define_rule!(
+    pub struct Call {
+        name: Ident,
+        l_paren: Discard<LParen>,
+        args: DelimitedList<Arg, Comma>,
+        r_paren: Discard<RParen>,
+    }
+    pub enum Arg {
+        Ident { ident: Ident },
+        Str { value: Str },
+    }
+    pub struct Str {
+        value: NoTrivia<StrBody>,
+    }
+    pub struct StrBody {
+        open: Discard<Quote>,
+        chars: Vec<StrChar>,
+        close: Discard<Quote>,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "(")]
+    pub struct LParen;
+    #[pattern(exact = ")")]
+    pub struct RParen;
+    #[pattern(exact = ",")]
+    pub struct Comma;
+    #[pattern(exact = "\"")]
+    pub struct Quote;
+    #[pattern(regex = r#"[^"]"#)]
+    pub struct StrChar;
+    #[pattern(regex = "[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+    #[pattern(regex = r"/\*[^*]*\*/")]
+    pub struct Comment;
+);
+
+fn parse(src: &str) -> Result<String, usize> {
+    let ast = ParseOptions::new()
+        .trivia::<Trivia>()
+        .parse_tree::<Call, 1>(src)
+        .map_err(|err| err.location.position)?;
+    Ok(format!("{:?}", WithSource { src, ast }))
+}
+
+#[test]
+pub fn trivia_test() {
+    let compact = parse("f(a,b)").unwrap();
+    assert_eq!(parse(" f ( a /* x */, /* y */ b ) ").unwrap(), compact);
+    assert_eq!(parse("f(\n  a,\n  b,\n)\n").unwrap(), compact);
+
+    // without trivia, the rules don't skip anything themselves
+    assert!(ParseOptions::new().parse_tree::<Call, 1>("f( a)").is_err());
+}
+
+#[test]
+pub fn no_trivia_test() {
+    let call = parse(r#"f( "a b" /* c */ , x)"#).unwrap();
+    assert!(call.contains(r#"<StrChar "a">, <StrChar " ">, <StrChar "b">"#));
+
+    // trivia is skipped before the string starts, but not inside it
+    assert_eq!(
+        parse(r#"f("a/**/")"#).unwrap().matches("StrChar").count(),
+        5
+    );
+    assert_eq!(parse(r#"f(" ")"#).unwrap().matches("StrChar").count(), 1);
+    assert_eq!(parse("f(a b)"), Err(4));
+}
+
+#[test]
+pub fn trivia_fuel_test() {
+    let fuel_used = |src| {
+        (0..)
+            .find(|&fuel| {
+                ParseOptions::new()
+                    .trivia::<Trivia>()
+                    .fuel(fuel)
+                    .parse_tree::<Call, 1>(src)
+                    .is_ok()
+            })
+            .unwrap()
+    };
+
+    // skipping trivia is part of the parse, so it's limited by the same fuel
+    let compact = fuel_used("f(a,b)");
+    assert!(fuel_used("f(a,/**//**//**//**/b)") > compact);
+    let err = ParseOptions::new()
+        .trivia::<Trivia>()
+        .fuel(compact)
+        .parse_tree::<Call, 1>("f(a,/**//**//**//**/b)")
+        .unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::OutOfFuel);
+}
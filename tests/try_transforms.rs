@@ -0,0 +1,248 @@
+#![allow(non_camel_case_types)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rs_typed_parser::{
+    ast::{transform::TryTransformInto, Backtrack, DelimitedList, Recover, Token},
+    parse::ParseErrorKind,
+    parse_tree, parse_tree_recover,
+};
+
+pub struct byte;
+
+impl TryTransformInto<u8> for byte {
+    type Input = Token<Digits>;
+
+    fn try_transform(input: Self::Input, src: &str) -> Result<u8, String> {
+        let text = input.range.slice(src);
+        text.parse()
+            .map_err(|_| format!("{text} doesn't fit in a byte"))
+    }
+}
+
+pub struct unique_names;
+
+impl TryTransformInto<Vec<String>> for unique_names {
+    type Input = DelimitedList<Token<Ident>, Comma, false>;
+
+    fn try_transform(input: Self::Input, src: &str) -> Result<Vec<String>, String> {
+        let mut names = Vec::<String>::new();
+        for token in input.items {
+            let name = token.range.slice(src);
+            if names.iter().any(|prev| prev == name) {
+                return Err(format!("duplicate name {name}"));
+            }
+            names.push(name.into());
+        }
+        Ok(names)
+    }
+}
+
+/// Like [`byte`], but counts how many times it's applied.
+pub struct counted_byte;
+
+static COUNTED: AtomicUsize = AtomicUsize::new(0);
+
+impl TryTransformInto<u8> for counted_byte {
+    type Input = Token<Digits>;
+
+    fn try_transform(input: Self::Input, src: &str) -> Result<u8, String> {
+        COUNTED.fetch_add(1, Ordering::Relaxed);
+        byte::try_transform(input, src)
+    }
+}
+
+rs_typed_parser::define_rule!(
+    pub struct Record {
+        l_brace: LBrace,
+        #[transform(try unique_names)]
+        names: Vec<String>,
+        r_brace: RBrace,
+    }
+    pub enum Number {
+        Byte {
+            #[transform(try byte)]
+            value: u8,
+        },
+        Other {
+            digits: Digits,
+        },
+    }
+    pub enum BacktrackingNumber {
+        Byte {
+            #[transform(try_transform<byte, true>)]
+            value: u8,
+        },
+        Other {
+            digits: Digits,
+        },
+    }
+    pub enum CountedNumber {
+        Byte {
+            #[transform(try_transform<counted_byte, true>)]
+            value: u8,
+        },
+        Other {
+            digits: Digits,
+        },
+    }
+    pub enum SpeculativeNumber {
+        Byte { item: Backtrack<ByteItem> },
+        Other { digits: Digits },
+    }
+    pub struct ByteItem {
+        #[transform(try byte)]
+        value: u8,
+    }
+    pub enum SpeculativeBacktrackingNumber {
+        Byte {
+            item: Backtrack<BacktrackingByteItem>,
+        },
+        Other {
+            digits: Digits,
+        },
+    }
+    pub struct BacktrackingByteItem {
+        #[transform(try_transform<byte, true>)]
+        value: u8,
+    }
+    pub struct Statements {
+        statements: Vec<Recover<Statement, Semi>>,
+    }
+    pub struct Statement {
+        #[transform(try byte)]
+        value: u8,
+        semi: Semi,
+    }
+    pub struct Bytes {
+        first: Number,
+        #[transform(for_each<compose<ignore_before<Space>, try_transform<byte>>>)]
+        rest: Vec<u8>,
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "{")]
+    pub struct LBrace;
+    #[pattern(exact = "}")]
+    pub struct RBrace;
+    #[pattern(exact = ",")]
+    pub struct Comma;
+    #[pattern(exact = ";")]
+    pub struct Semi;
+    #[pattern(regex = "[a-z]+")]
+    pub struct Ident;
+    #[pattern(regex = "[0-9]+")]
+    pub struct Digits;
+    #[pattern(regex = r"\s+")]
+    pub struct Space;
+);
+
+fn invalid<T: rs_typed_parser::Rule>(src: &str) -> (&str, String) {
+    let err = parse_tree::<T, 1>(src).unwrap_err();
+    let ParseErrorKind::Invalid { range, message } = err.kind else {
+        panic!("expected an invalid value, got {:?}", err.kind);
+    };
+    assert_eq!(err.location, range.start);
+    (range.slice(src), message)
+}
+
+#[test]
+pub fn try_transform_test() {
+    let record = parse_tree::<Record, 1>("{a,b,c}").unwrap();
+    assert_eq!(record.names, ["a", "b", "c"]);
+
+    assert_eq!(
+        invalid::<Record>("{a,b,a}"),
+        ("a,b,a", "duplicate name a".into())
+    );
+
+    let bytes = parse_tree::<Bytes, 1>("1 2 3").unwrap();
+    assert!(matches!(bytes.first, Number::Byte { value: 1 }));
+    assert_eq!(bytes.rest, [2, 3]);
+    assert_eq!(
+        invalid::<Bytes>("1 2 300 4"),
+        ("300", "300 doesn't fit in a byte".into())
+    );
+}
+
+#[test]
+pub fn backtrack_test() {
+    // without backtracking, the first alternative is committed to before its value is checked
+    assert_eq!(
+        invalid::<Number>("300"),
+        ("300", "300 doesn't fit in a byte".into())
+    );
+
+    assert!(matches!(
+        parse_tree::<BacktrackingNumber, 1>("200").unwrap(),
+        BacktrackingNumber::Byte { value: 200 }
+    ));
+    assert!(matches!(
+        parse_tree::<BacktrackingNumber, 1>("300").unwrap(),
+        BacktrackingNumber::Other { .. }
+    ));
+}
+
+#[test]
+pub fn backtrack_once_test() {
+    // the value checked ahead of time is reused rather than transformed again
+    COUNTED.store(0, Ordering::Relaxed);
+    assert!(matches!(
+        parse_tree::<CountedNumber, 1>("200").unwrap(),
+        CountedNumber::Byte { value: 200 }
+    ));
+    assert_eq!(COUNTED.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+pub fn speculative_test() {
+    // a rejected value isn't a mismatch, even when it's only parsed to pick an alternative
+    assert_eq!(
+        invalid::<SpeculativeNumber>("300"),
+        ("300", "300 doesn't fit in a byte".into())
+    );
+    assert!(matches!(
+        parse_tree::<SpeculativeNumber, 1>("30").unwrap(),
+        SpeculativeNumber::Byte { .. }
+    ));
+
+    // unless it's asked to backtrack
+    assert!(matches!(
+        parse_tree::<SpeculativeBacktrackingNumber, 1>("300").unwrap(),
+        SpeculativeBacktrackingNumber::Other { .. }
+    ));
+    assert!(matches!(
+        parse_tree::<SpeculativeBacktrackingNumber, 1>("30").unwrap(),
+        SpeculativeBacktrackingNumber::Byte { .. }
+    ));
+}
+
+#[test]
+pub fn recover_test() {
+    let src = "1;300;2;400;3;";
+    let (statements, errors) = parse_tree_recover::<Statements, 1>(src);
+
+    let values = statements
+        .unwrap()
+        .statements
+        .iter()
+        .map(|statement| statement.ok().map(|statement| statement.value))
+        .collect::<Vec<_>>();
+    assert_eq!(values, [Some(1), None, Some(2), None, Some(3)]);
+
+    let errors = errors
+        .iter()
+        .map(|err| match &err.kind {
+            ParseErrorKind::Invalid { range, message } => (range.slice(src), message.as_str()),
+            kind => panic!("expected an invalid value, got {kind:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            ("300", "300 doesn't fit in a byte"),
+            ("400", "400 doesn't fit in a byte")
+        ]
+    );
+}
@@ -0,0 +1,76 @@
+use rs_typed_parser::{ast::Backtrack, parse::ParseErrorKind, parse_tree};
+
+rs_typed_parser::define_rule!(
+    pub struct Pair {
+        l_paren: LParen,
+        first: Int,
+        comma: Comma,
+        second: Hex,
+        r_paren: RParen,
+    }
+    pub enum Number {
+        Byte { value: Backtrack<Int> },
+        Other { digits: Digits },
+    }
+);
+
+rs_typed_parser::define_token!(
+    #[pattern(exact = "(")]
+    pub struct LParen;
+    #[pattern(exact = ")")]
+    pub struct RParen;
+    #[pattern(exact = ",")]
+    pub struct Comma;
+    #[pattern(regex = r"[0-9]+")]
+    pub struct Int(u8);
+    #[pattern(regex = r"0x[0-9a-fA-F]+")]
+    #[convert(parse_hex)]
+    /// A hexadecimal number.
+    pub struct Hex(u32);
+    #[pattern(regex = r"[0-9]+")]
+    pub struct Digits;
+);
+
+fn parse_hex(text: &str) -> Result<u32, std::num::ParseIntError> {
+    u32::from_str_radix(&text[2..], 16)
+}
+
+#[test]
+pub fn value_token_test() {
+    let pair = parse_tree::<Pair, 1>("(42,0xff)").unwrap();
+    assert_eq!(pair.first.0, 42);
+    assert_eq!(pair.second.0, 255);
+}
+
+#[test]
+pub fn value_token_error_test() {
+    let src = "(1000,0x1)";
+    let err = parse_tree::<Pair, 1>(src).unwrap_err();
+    let ParseErrorKind::Invalid { range, message } = &err.kind else {
+        panic!("expected an invalid value, got {:?}", err.kind);
+    };
+    assert_eq!(range.slice(src), "1000");
+    assert_eq!(message, "number too large to fit in target type");
+    assert_eq!(err.actual, "1000");
+    assert!(err
+        .display()
+        .to_string()
+        .ends_with("1 | (1000,0x1)\n  |  ^^^^"));
+
+    let err = parse_tree::<Pair, 1>("(1,0x100000000)").unwrap_err();
+    assert!(matches!(err.kind, ParseErrorKind::Invalid { .. }));
+    assert_eq!(err.actual, "0x100000000");
+}
+
+#[test]
+pub fn value_token_backtrack_test() {
+    // an invalid value only fails the alternative that's being tried
+    assert!(matches!(
+        parse_tree::<Number, 1>("200").unwrap(),
+        Number::Byte { value } if value.value.0 == 200
+    ));
+    assert!(matches!(
+        parse_tree::<Number, 1>("300").unwrap(),
+        Number::Other { .. }
+    ));
+}